@@ -4,42 +4,108 @@ use std::env;
 fn main() {
     println!("cargo:rerun-if-changed=lzsa-upstream/src/");
     println!("cargo:rerun-if-changed=csrc/");
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+
+    // With the system-lib feature only our wrapper is compiled, against a
+    // system-installed LZSA found through pkg-config
+    #[cfg(feature = "system-lib")]
+    let system_lib = Some(
+        pkg_config::Config::new()
+            .cargo_metadata(false)
+            .probe("lzsa")
+            .unwrap_or_else(|e| panic!("system-lib: could not find LZSA with pkg-config: {e}")),
+    );
+    #[cfg(not(feature = "system-lib"))]
+    let system_lib: Option<()> = None;
+
+    // Block formats to build (the v1/v2 features)
+    let with_v1 = env::var_os("CARGO_FEATURE_V1").is_some();
+    let with_v2 = env::var_os("CARGO_FEATURE_V2").is_some();
+
+    // The fast-only feature swaps libdivsufsort for csrc/lzsa_sufsort.c
+    let fast_only = env::var_os("CARGO_FEATURE_FAST_ONLY").is_some();
 
     // List of all C files needed from the LZSA project
-    let sources = vec![
-        // Our wrapper
-        "csrc/lzsa_wrapper.c",
+    let mut upstream_sources = vec![
         // Compression
         "lzsa-upstream/src/shrink_inmem.c",
         "lzsa-upstream/src/shrink_context.c",
-        "lzsa-upstream/src/shrink_block_v1.c",
-        "lzsa-upstream/src/shrink_block_v2.c",
         "lzsa-upstream/src/matchfinder.c",
         // Decompression
         "lzsa-upstream/src/expand_inmem.c",
         "lzsa-upstream/src/expand_context.c",
-        "lzsa-upstream/src/expand_block_v1.c",
-        "lzsa-upstream/src/expand_block_v2.c",
         // Support modules
         "lzsa-upstream/src/frame.c",
         // NOTE: format.c does not exist - it's just format.h with constants
-        // libdivsufsort (suffix array library)
-        "lzsa-upstream/src/libdivsufsort/lib/divsufsort.c",
-        "lzsa-upstream/src/libdivsufsort/lib/divsufsort_utils.c",
-        "lzsa-upstream/src/libdivsufsort/lib/sssort.c",
-        "lzsa-upstream/src/libdivsufsort/lib/trsort.c",
     ];
+    if fast_only {
+        upstream_sources.push("csrc/lzsa_sufsort.c");
+    } else {
+        // libdivsufsort (suffix array library)
+        upstream_sources.extend([
+            "lzsa-upstream/src/libdivsufsort/lib/divsufsort.c",
+            "lzsa-upstream/src/libdivsufsort/lib/divsufsort_utils.c",
+            "lzsa-upstream/src/libdivsufsort/lib/sssort.c",
+            "lzsa-upstream/src/libdivsufsort/lib/trsort.c",
+        ]);
+    }
+    if with_v1 {
+        upstream_sources.push("lzsa-upstream/src/shrink_block_v1.c");
+        upstream_sources.push("lzsa-upstream/src/expand_block_v1.c");
+    }
+    if with_v2 {
+        upstream_sources.push("lzsa-upstream/src/shrink_block_v2.c");
+        upstream_sources.push("lzsa-upstream/src/expand_block_v2.c");
+    }
 
     let mut build = cc::Build::new();
 
-    // Add all source files
-    build.files(&sources);
+    // Our wrapper
+    build.file("csrc/lzsa_wrapper.c").include("csrc");
+
+    // The wrapper rejects versions that are left out
+    if !with_v1 {
+        build.define("LZSA_NO_V1", None);
+    }
+    if !with_v2 {
+        build.define("LZSA_NO_V2", None);
+    }
+
+    #[cfg(feature = "system-lib")]
+    if let Some(library) = &system_lib {
+        build.includes(&library.include_paths);
+    }
+    if fast_only && system_lib.is_some() {
+        println!("cargo:warning=the fast-only feature has no effect with system-lib");
+    }
 
-    // Include paths
-    build
-        .include("csrc")
-        .include("lzsa-upstream/src")
-        .include("lzsa-upstream/src/libdivsufsort/include");
+    if system_lib.is_none() {
+        // Add all upstream source files, plus stand-ins for the entry
+        // points of a left-out block format
+        build.files(&upstream_sources);
+        build.file("csrc/lzsa_version_stubs.c");
+
+        // Include paths
+        build
+            .include("lzsa-upstream/src")
+            .include("lzsa-upstream/src/libdivsufsort/include");
+    }
+
+    // Redirect malloc/free in the C code to the Rust allocator hook
+    if env::var_os("CARGO_FEATURE_ALLOC_HOOK").is_some() {
+        let header = std::path::Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap())
+            .join("csrc/lzsa_alloc.h");
+        if build.get_compiler().is_like_msvc() {
+            build.flag(format!("/FI{}", header.display()));
+        } else {
+            build.flag("-include").flag(header.to_str().unwrap());
+        }
+        if system_lib.is_some() {
+            println!(
+                "cargo:warning=alloc-hook only covers the wrapper's allocations with system-lib"
+            );
+        }
+    }
 
     // Check if we're building for release or debug
     let profile = env::var("PROFILE").unwrap_or_else(|_| "debug".to_string());
@@ -47,31 +113,189 @@ fn main() {
 
     // Platform-specific optimization
     let target = env::var("TARGET").unwrap();
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+
+    // Tuning for the build machine's CPU is opt-in: binaries built with it
+    // may crash with illegal instructions on older CPUs
+    let native_cpu = env::var_os("CARGO_FEATURE_NATIVE_CPU").is_some();
+
+    // AddressSanitizer instrumentation of the C code, for testing only
+    let asan = env::var_os("CARGO_FEATURE_ASAN").is_some();
+
+    // Link-time optimization of the C code is opt-in too: the objects it
+    // produces need a linker that understands them
+    let mut lto = is_release && env::var_os("CARGO_FEATURE_LTO").is_some();
+    if lto && asan {
+        println!("cargo:warning=lto is ignored with asan, which does not support it");
+        lto = false;
+    }
 
     if target.contains("windows") && target.contains("msvc") {
         // Windows MSVC
         build.define("_CRT_SECURE_NO_WARNINGS", None);
 
+        if native_cpu {
+            println!("cargo:warning=the native-cpu feature has no effect with MSVC");
+        }
+        if asan {
+            println!("cargo:warning=the asan feature has no effect with MSVC");
+        }
+
         if is_release {
             build.flag("/O2"); // Maximum speed optimization
+            if lto {
+                build.flag("/GL"); // link.exe switches to /LTCG for these
+            }
         } else {
             build.flag("/Od"); // No optimization for debug
         }
+    } else if target_arch == "wasm32" {
+        // Clang targeting WebAssembly: there is no host CPU to tune for and
+        // no frame pointer register to free up
+        if native_cpu {
+            println!("cargo:warning=the native-cpu feature has no effect on wasm32");
+        }
+        if asan {
+            println!("cargo:warning=the asan feature has no effect on wasm32");
+        }
+
+        if is_release {
+            build.flag("-O3");
+        } else {
+            build.flag("-O0");
+            build.flag("-g");
+        }
     } else {
         // GCC/Clang (Linux, macOS, MinGW)
         if is_release {
             build.flag("-O3"); // Maximum optimization
             build.flag("-fomit-frame-pointer"); // Extra performance
-        // NOTE: Do NOT use -march=native (breaks portability)
-        // NOTE: Do NOT use -flto (conflicts with Rust's LTO)
+            if native_cpu {
+                build.flag("-march=native"); // Only with the native-cpu feature
+            }
+            if lto {
+                add_lto_flags(&mut build);
+            }
         } else {
             build.flag("-O0"); // No optimization for debug
             build.flag("-g"); // Debug symbols
         }
+        if asan {
+            add_asan_flags(&mut build);
+        }
+    }
+
+    // Paths for the build scripts of crates that link against this one, as
+    // DEP_LZSA_ROOT, DEP_LZSA_INCLUDE and DEP_LZSA_LIB
+    let manifest_dir = std::path::PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut include_dirs = vec![manifest_dir.join("csrc")];
+    if system_lib.is_none() {
+        include_dirs.push(manifest_dir.join("lzsa-upstream/src"));
+        include_dirs.push(manifest_dir.join("lzsa-upstream/src/libdivsufsort/include"));
+    }
+    #[cfg(feature = "system-lib")]
+    if let Some(library) = &system_lib {
+        include_dirs.extend(library.include_paths.iter().cloned());
     }
+    let out_dir = env::var("OUT_DIR").unwrap();
+    println!("cargo:root={out_dir}");
+    println!("cargo:lib={out_dir}");
+    println!(
+        "cargo:include={}",
+        env::join_paths(&include_dirs).unwrap().to_str().unwrap()
+    );
 
     // Compile to static library
-    build.compile("lzsa");
+    if system_lib.is_none() {
+        build.compile("lzsa");
+
+        println!("cargo:rustc-link-lib=static=lzsa");
+    } else {
+        // Named apart from the system library, which must be linked after it
+        build.compile("lzsa_wrapper");
+    }
+
+    // Optionally generate bindings from the wrapper header, so tests can
+    // check the hand-written declarations in src/lib.rs against it
+    #[cfg(feature = "bindgen")]
+    generate_bindings();
+
+    #[cfg(feature = "system-lib")]
+    if let Some(library) = &system_lib {
+        for path in &library.link_paths {
+            println!("cargo:rustc-link-search=native={}", path.display());
+        }
+        for lib in &library.libs {
+            println!("cargo:rustc-link-lib={lib}");
+        }
+    }
+}
+
+/// Add -flto for GCC/Clang, or warn and build without it where the result
+/// would likely not link
+///
+/// GCC also emits regular code next to its LTO data (-ffat-lto-objects), so
+/// the library links even when the linker cannot optimize across it. Clang
+/// only emits LLVM bitcode, which rustc links through its linker plugin
+/// (`-C linker-plugin-lto`) and nothing else.
+fn add_lto_flags(build: &mut cc::Build) {
+    if !build.is_flag_supported("-flto").unwrap_or(false) {
+        println!("cargo:warning=lto: the C compiler does not accept -flto, building without it");
+        return;
+    }
+
+    if build.get_compiler().is_like_clang() {
+        let rustflags = env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default();
+        if !rustflags.contains("linker-plugin-lto") {
+            println!(
+                "cargo:warning=lto: Clang needs RUSTFLAGS=\"-C linker-plugin-lto\" to link LTO objects, building without it"
+            );
+            return;
+        }
+        build.flag("-flto=thin");
+    } else {
+        build.flag("-flto");
+        build.flag("-ffat-lto-objects");
+    }
+}
+
+/// Instrument the C code with AddressSanitizer and link its runtime
+///
+/// rustc links with -nodefaultlibs, which keeps GCC from adding libasan for
+/// -fsanitize=address, so it is linked by name; listed ahead of the system
+/// libraries, it comes first at load time as the runtime requires. Clang
+/// adds its own runtime for the flag. The link argument only reaches this
+/// package's tests, examples and benchmarks.
+fn add_asan_flags(build: &mut cc::Build) {
+    if !build
+        .is_flag_supported("-fsanitize=address")
+        .unwrap_or(false)
+    {
+        panic!("asan: the C compiler does not accept -fsanitize=address");
+    }
+
+    build.flag("-fsanitize=address");
+    build.flag("-fno-omit-frame-pointer"); // Complete stack traces in reports
+    build.define("LZSA_ASAN", None); // Runtime defaults in the wrapper
+    println!("cargo:rustc-link-arg=-fsanitize=address");
+    if !build.get_compiler().is_like_clang() {
+        println!("cargo:rustc-link-lib=dylib=asan");
+    }
+}
+
+#[cfg(feature = "bindgen")]
+fn generate_bindings() {
+    let out_dir = std::path::PathBuf::from(env::var("OUT_DIR").unwrap());
 
-    println!("cargo:rustc-link-lib=static=lzsa");
+    bindgen::Builder::default()
+        .header("csrc/lzsa_wrapper.h")
+        .allowlist_function("lzsa_.*")
+        .allowlist_type("lzsa.*")
+        .prepend_enum_name(false)
+        .use_core()
+        .rust_edition(bindgen::RustEdition::Edition2024)
+        .generate()
+        .expect("failed to generate bindings for csrc/lzsa_wrapper.h")
+        .write_to_file(out_dir.join("bindings.rs"))
+        .expect("failed to write bindings");
 }
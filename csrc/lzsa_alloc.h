@@ -0,0 +1,37 @@
+/**
+ * lzsa_alloc.h - Route C heap allocations through the Rust crate
+ *
+ * With the alloc-hook feature, build.rs force-includes this header into
+ * every C file it compiles (-include, or /FI with MSVC), ahead of any other
+ * include. malloc, calloc, realloc and free then resolve to the functions
+ * below, which are defined on the Rust side (src/alloc_hook.rs).
+ *
+ * <stdlib.h> is included first so that its own declarations are not
+ * renamed; its include guard keeps later includes from seeing the macros.
+ */
+
+#ifndef LZSA_ALLOC_H
+#define LZSA_ALLOC_H
+
+#include <stddef.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+void* lzsa_alloc_malloc(size_t size);
+void* lzsa_alloc_calloc(size_t count, size_t size);
+void* lzsa_alloc_realloc(void* ptr, size_t size);
+void lzsa_alloc_free(void* ptr);
+
+#ifdef __cplusplus
+}
+#endif
+
+#define malloc(size) lzsa_alloc_malloc(size)
+#define calloc(count, size) lzsa_alloc_calloc(count, size)
+#define realloc(ptr, size) lzsa_alloc_realloc(ptr, size)
+#define free(ptr) lzsa_alloc_free(ptr)
+
+#endif /* LZSA_ALLOC_H */
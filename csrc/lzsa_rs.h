@@ -0,0 +1,100 @@
+/**
+ * lzsa_rs.h - C API of the lzsa-sys Rust crate (capi feature)
+ *
+ * These functions wrap the crate's safe Rust functions, so streams are
+ * checked on the Rust side (truncation, oversized block frames, output
+ * sizes reported by the codec) before any output is trusted. They take the
+ * same options and return the same LZSAWRAP_* codes as lzsa_wrapper.h,
+ * plus the LZSA_RS_ERR_* codes below for errors only the Rust side
+ * detects. Link against the library built with
+ *   cargo rustc --release --features capi --crate-type staticlib
+ *
+ * Kept in sync with src/capi.rs by a test.
+ */
+
+#ifndef LZSA_RS_H
+#define LZSA_RS_H
+
+#include "lzsa_wrapper.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * Error codes for errors that only the Rust side reports
+ */
+enum {
+    LZSA_RS_ERR_CHECKSUM_MISMATCH = -101,
+    LZSA_RS_ERR_OUT_OF_RANGE = -102,
+    LZSA_RS_ERR_OUTPUT_TOO_LARGE = -103,
+    LZSA_RS_ERR_INVALID_UTF8 = -104,
+    LZSA_RS_ERR_TRUNCATED = -105,   /* Stream ends before its footer */
+    LZSA_RS_ERR_IO = -106,
+};
+
+/**
+ * Compress data into a caller-provided buffer
+ *
+ * @param input Input data (may be NULL if input_size is 0)
+ * @param input_size Size of input data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: buffer size, out: actual size)
+ * @param options Compression options, or NULL for the defaults
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+int lzsa_rs_compress(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options
+);
+
+/**
+ * Decompress a framed stream (LZSA1 or LZSA2) into a caller-provided buffer
+ *
+ * @param input Compressed data (may be NULL if input_size is 0)
+ * @param input_size Size of compressed data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: buffer size, out: actual size)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+int lzsa_rs_decompress(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size
+);
+
+/**
+ * Get the worst-case compressed size for the given options
+ *
+ * @param input_size Size of input data
+ * @param options Compression options
+ * @return Maximum compressed size, or 0 if options are NULL or invalid
+ */
+size_t lzsa_rs_max_compressed_size(
+    size_t input_size,
+    const lzsa_options_t* options
+);
+
+/**
+ * Get an upper bound of the decompressed size from the frame headers
+ *
+ * @param input Compressed data (may be NULL if input_size is 0)
+ * @param input_size Size of compressed data
+ * @param output_size Receives the bound
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+int lzsa_rs_max_decompressed_size(
+    const uint8_t* input,
+    size_t input_size,
+    size_t* output_size
+);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LZSA_RS_H */
@@ -0,0 +1,93 @@
+/**
+ * lzsa_sufsort.c - Small suffix array builder replacing libdivsufsort
+ *
+ * Compiled instead of the libdivsufsort sources with the fast-only feature.
+ * It implements the three entry points the LZSA match finder calls, using
+ * prefix doubling with radix sorting: O(n log n) time and three extra
+ * integer arrays, in a fraction of the code. The suffix array it produces
+ * is exact, so the compressor finds the same matches; building it is
+ * slower than libdivsufsort on large, repetitive windows.
+ */
+
+#include "divsufsort.h"
+#include <stdlib.h>
+#include <string.h>
+
+saint_t divsufsort_init(divsufsort_ctx_t *ctx) {
+    /* No state is kept between calls */
+    (void)ctx;
+    return 0;
+}
+
+void divsufsort_destroy(divsufsort_ctx_t *ctx) {
+    (void)ctx;
+}
+
+saint_t divsufsort_build_array(divsufsort_ctx_t *ctx, const sauchar_t *T, saidx_t *SA, saidx_t n) {
+    (void)ctx;
+
+    if (!T || !SA || n < 0) return -1;
+    if (n <= 1) {
+        if (n == 1) SA[0] = 0;
+        return 0;
+    }
+
+    size_t count_size = (size_t)(n > 256 ? n : 256);
+    saidx_t *rank = (saidx_t *)malloc((size_t)n * sizeof(saidx_t));
+    saidx_t *next = (saidx_t *)malloc((size_t)n * sizeof(saidx_t));
+    saidx_t *count = (saidx_t *)malloc(count_size * sizeof(saidx_t));
+    if (!rank || !next || !count) {
+        free(rank);
+        free(next);
+        free(count);
+        return -2;
+    }
+
+    /* Sort by the first byte */
+    saidx_t i;
+    memset(count, 0, 256 * sizeof(saidx_t));
+    for (i = 0; i < n; i++) count[T[i]]++;
+    for (i = 1; i < 256; i++) count[i] += count[i - 1];
+    for (i = n - 1; i >= 0; i--) SA[--count[T[i]]] = i;
+    for (i = 0; i < n; i++) rank[i] = T[i];
+    saidx_t classes = 256;
+
+    /* Suffixes are in order of their first k bytes; double k until every
+     * rank is distinct */
+    for (saidx_t k = 1; ; k *= 2) {
+        /* Order by the second half: suffixes that end within it (an empty
+         * second half sorts first), then the others as SA orders them */
+        saidx_t p = 0;
+        for (i = n - k; i < n; i++) next[p++] = i;
+        for (i = 0; i < n; i++) {
+            if (SA[i] >= k) next[p++] = SA[i] - k;
+        }
+
+        /* Stable counting sort by the first half */
+        memset(count, 0, (size_t)classes * sizeof(saidx_t));
+        for (i = 0; i < n; i++) count[rank[i]]++;
+        for (i = 1; i < classes; i++) count[i] += count[i - 1];
+        for (i = n - 1; i >= 0; i--) SA[--count[rank[next[i]]]] = next[i];
+
+        /* Rerank by both halves */
+        saidx_t current = 0;
+        next[SA[0]] = 0;
+        for (i = 1; i < n; i++) {
+            saidx_t a = SA[i - 1];
+            saidx_t b = SA[i];
+            saidx_t second_a = (a + k < n) ? rank[a + k] : -1;
+            saidx_t second_b = (b + k < n) ? rank[b + k] : -1;
+            if (rank[a] != rank[b] || second_a != second_b) current++;
+            next[b] = current;
+        }
+        memcpy(rank, next, (size_t)n * sizeof(saidx_t));
+
+        classes = current + 1;
+        if (classes == n || k >= n) break;
+    }
+
+    free(rank);
+    free(next);
+    free(count);
+    return 0;
+}
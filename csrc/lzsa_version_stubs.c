@@ -0,0 +1,48 @@
+/**
+ * lzsa_version_stubs.c - Stand-ins for LZSA block formats left out of the build
+ *
+ * The upstream compressor and decompressor contexts dispatch to both block
+ * formats, so building with only one of the v1/v2 features still needs the
+ * other's entry points. These fail like a rejected block would; the wrapper
+ * refuses the disabled version before they can be reached.
+ */
+
+#include "shrink_context.h"
+#include "shrink_block_v1.h"
+#include "shrink_block_v2.h"
+#include "expand_block_v1.h"
+#include "expand_block_v2.h"
+
+#ifdef LZSA_NO_V1
+int lzsa_optimize_and_write_block_v1(lzsa_compressor *pCompressor, const unsigned char *pInWindow,
+                                     const int nPreviousBlockSize, const int nInDataSize,
+                                     unsigned char *pOutData, const int nMaxOutDataSize) {
+    (void)pCompressor; (void)pInWindow; (void)nPreviousBlockSize;
+    (void)nInDataSize; (void)pOutData; (void)nMaxOutDataSize;
+    return -1;
+}
+
+int lzsa_decompressor_expand_block_v1(const unsigned char *pInBlock, int nBlockSize,
+                                      unsigned char *pOutData, int nOutDataOffset,
+                                      int nBlockMaxSize) {
+    (void)pInBlock; (void)nBlockSize; (void)pOutData; (void)nOutDataOffset; (void)nBlockMaxSize;
+    return -1;
+}
+#endif
+
+#ifdef LZSA_NO_V2
+int lzsa_optimize_and_write_block_v2(lzsa_compressor *pCompressor, const unsigned char *pInWindow,
+                                     const int nPreviousBlockSize, const int nInDataSize,
+                                     unsigned char *pOutData, const int nMaxOutDataSize) {
+    (void)pCompressor; (void)pInWindow; (void)nPreviousBlockSize;
+    (void)nInDataSize; (void)pOutData; (void)nMaxOutDataSize;
+    return -1;
+}
+
+int lzsa_decompressor_expand_block_v2(const unsigned char *pInBlock, int nBlockSize,
+                                      unsigned char *pOutData, int nOutDataOffset,
+                                      int nBlockMaxSize) {
+    (void)pInBlock; (void)nBlockSize; (void)pOutData; (void)nOutDataOffset; (void)nBlockMaxSize;
+    return -1;
+}
+#endif
@@ -7,6 +7,8 @@
 
 #include "lzsa_wrapper.h"
 #include "lib.h"
+#include <limits.h>
+#include <stddef.h>
 #include <string.h>
 #include <stdlib.h>
 
@@ -16,10 +18,16 @@
 
 lzsa_options_t lzsa_get_default_options(void) {
     lzsa_options_t options;
+#ifdef LZSA_NO_V1
+    options.version = LZSA_VERSION_2;
+#else
     options.version = LZSA_VERSION_1;
+#endif
     options.mode = LZSA_MODE_NORMAL;
     options.quality = LZSA_QUALITY_RATIO;
     options.min_match_size = 3;
+    options.effort = LZSA_EFFORT_FROM_QUALITY;
+    options.block_size = BLOCK_SIZE;
     return options;
 }
 
@@ -39,7 +47,9 @@ size_t lzsa_get_max_decompressed_size(
     const uint8_t* compressed_data,
     size_t compressed_size)
 {
-    if (!compressed_data) {
+    /* A stream is at least a header and a footer */
+    if (!compressed_data ||
+        compressed_size < (size_t)(lzsa_get_header_size() + lzsa_get_frame_size())) {
         return 0;
     }
 
@@ -60,10 +70,33 @@ size_t lzsa_get_max_decompressed_size(
  * Internal Helper
  * ============================================================================ */
 
+/* Versions left out of the build (LZSA_NO_V1/LZSA_NO_V2) are rejected too */
+static int lzsa_version_is_supported(lzsa_version_t version) {
+    switch (version) {
+#ifndef LZSA_NO_V1
+        case LZSA_VERSION_1: return 1;
+#endif
+#ifndef LZSA_NO_V2
+        case LZSA_VERSION_2: return 1;
+#endif
+        default: return 0;
+    }
+}
+
+/* Effort level 0-9; levels above 9 are clamped */
+static unsigned int lzsa_options_to_effort(const lzsa_options_t* options) {
+    if (options->effort == LZSA_EFFORT_FROM_QUALITY) {
+        return options->quality == LZSA_QUALITY_RATIO ? 9 : 0;
+    }
+
+    return options->effort > 9 ? 9 : options->effort;
+}
+
 static unsigned int lzsa_options_to_flags(const lzsa_options_t* options) {
     unsigned int flags = 0;
 
-    if (options->quality == LZSA_QUALITY_RATIO) {
+    /* The library has a single optimizer switch, so the levels split in two */
+    if (lzsa_options_to_effort(options) >= 5) {
         flags |= LZSA_FLAG_FAVOR_RATIO;
     }
 
@@ -71,9 +104,36 @@ static unsigned int lzsa_options_to_flags(const lzsa_options_t* options) {
         flags |= LZSA_FLAG_RAW_BLOCK;
     }
 
+    if (options->mode == LZSA_MODE_RAW_BACKWARD) {
+        flags |= LZSA_FLAG_RAW_BLOCK | LZSA_FLAG_RAW_BACKWARD;
+    }
+
     return flags;
 }
 
+static int lzsa_min_match_is_valid(const lzsa_options_t* options) {
+    uint32_t min = (options->version == LZSA_VERSION_2) ? MIN_MATCH_SIZE_V2 : MIN_MATCH_SIZE_V1;
+    return options->min_match_size >= min && options->min_match_size <= LZSA_MAX_MIN_MATCH_SIZE;
+}
+
+static int lzsa_block_size_is_valid(const lzsa_options_t* options) {
+    return options->block_size >= 1 && options->block_size <= BLOCK_SIZE;
+}
+
+static int lzsa_options_to_min_match(const lzsa_options_t* options) {
+    return (int)options->min_match_size;
+}
+
+/* Framed block loop shared with the statistics and dictionary APIs below */
+static lzsawrap_error_t lzsa_compress_framed(
+    unsigned char* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options,
+    lzsa_stats_t* stats);
+
 /* ============================================================================
  * Compression API
  * ============================================================================ */
@@ -92,26 +152,53 @@ lzsawrap_error_t lzsa_compress(
     if (!options) return LZSAWRAP_ERR_INVALID_MODE;
 
     /* Validate version */
-    if (options->version != LZSA_VERSION_1 && options->version != LZSA_VERSION_2) {
+    if (!lzsa_version_is_supported(options->version)) {
         return LZSAWRAP_ERR_INVALID_VERSION;
     }
 
+    if (!lzsa_min_match_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_MIN_MATCH;
+    }
+
+    if (!lzsa_block_size_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
     /* Handle empty input */
     if (input_size == 0) {
         *output_size = 0;
         return LZSAWRAP_OK;
     }
 
-    /* Raw blocks have a size limit of 64KB */
-    if (options->mode == LZSA_MODE_RAW_FORWARD && input_size > 65536) {
-        return LZSAWRAP_ERR_COMPRESSION_FAILED;
+    if (options->mode != LZSA_MODE_NORMAL &&
+        options->mode != LZSA_MODE_RAW_FORWARD &&
+        options->mode != LZSA_MODE_RAW_BACKWARD) {
+        return LZSAWRAP_ERR_INVALID_MODE;
+    }
+
+    /* Raw blocks have a size limit of 64KB; framed streams split into blocks */
+    if (options->mode != LZSA_MODE_NORMAL && input_size > BLOCK_SIZE) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    /* The library only emits full-size blocks */
+    if (options->mode == LZSA_MODE_NORMAL && options->block_size != BLOCK_SIZE) {
+        unsigned char* window = (unsigned char*)malloc(input_size);
+        if (!window) {
+            return LZSAWRAP_ERR_OUT_OF_MEMORY;
+        }
+        memcpy(window, input, input_size);
+
+        lzsawrap_error_t error = lzsa_compress_framed(window, 0, input_size,
+                                                      output, output_size, options, NULL);
+
+        free(window);
+        return error;
     }
 
     /* Convert options to flags */
     unsigned int flags = lzsa_options_to_flags(options);
-    int min_match = (int)options->min_match_size;
-    if (min_match < 3) min_match = 3;
-    if (min_match > 5) min_match = 5;
+    int min_match = lzsa_options_to_min_match(options);
 
     /* lzsa_compress_inmem modifies the input buffer when using backward mode,
      * so we ALWAYS need to make a copy for safety */
@@ -169,12 +256,15 @@ lzsawrap_error_t lzsa_compress_v2(
  * Decompression API
  * ============================================================================ */
 
-lzsawrap_error_t lzsa_decompress(
+/* lzsa_decompress with a caller-owned buffer of at least input_size bytes
+ * for the input copy, or NULL to allocate one for the call */
+static lzsawrap_error_t lzsa_decompress_using(
     const uint8_t* input,
     size_t input_size,
     uint8_t* output,
     size_t* output_size,
-    lzsa_version_t* detected_version)
+    lzsa_version_t* detected_version,
+    unsigned char* scratch)
 {
     /* Validate inputs */
     if (!input) return LZSAWRAP_ERR_INPUT_NULL;
@@ -190,8 +280,19 @@ lzsawrap_error_t lzsa_decompress(
         return LZSAWRAP_OK;
     }
 
+    if (input_size < (size_t)(lzsa_get_header_size() + lzsa_get_frame_size())) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    /* Versions left out of the build would only fail deep in the library */
+    int header_version = 0;
+    if (lzsa_decode_header((const unsigned char*)input, lzsa_get_header_size(), &header_version) == 0 &&
+        !lzsa_version_is_supported((lzsa_version_t)header_version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
     /* Make a mutable copy for the library (it modifies input for some reason) */
-    unsigned char* input_copy = (unsigned char*)malloc(input_size);
+    unsigned char* input_copy = scratch ? scratch : (unsigned char*)malloc(input_size);
     if (!input_copy) {
         return LZSAWRAP_ERR_OUT_OF_MEMORY;
     }
@@ -210,13 +311,20 @@ lzsawrap_error_t lzsa_decompress(
         &format_version
     );
 
-    free(input_copy);
+    if (input_copy != scratch) {
+        free(input_copy);
+    }
 
     /* Check for error */
     if (result == (size_t)-1) {
         return LZSAWRAP_ERR_DECOMPRESSION_FAILED;
     }
 
+    /* Never report more output than the buffer holds */
+    if (result > *output_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
     *output_size = result;
 
     if (detected_version) {
@@ -226,6 +334,17 @@ lzsawrap_error_t lzsa_decompress(
     return LZSAWRAP_OK;
 }
 
+lzsawrap_error_t lzsa_decompress(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t* detected_version)
+{
+    return lzsa_decompress_using(input, input_size, output, output_size,
+                                 detected_version, NULL);
+}
+
 lzsawrap_error_t lzsa_decompress_v1(
     const uint8_t* input,
     size_t input_size,
@@ -274,6 +393,10 @@ lzsawrap_error_t lzsa_decompress_v1_backward(
         return LZSAWRAP_OK;
     }
 
+    if (!lzsa_version_is_supported(LZSA_VERSION_1)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
     /* For backward raw blocks, we need a large enough buffer */
     if (*output_size < 65536) {
         return LZSAWRAP_ERR_BUFFER_TOO_SMALL;
@@ -304,6 +427,11 @@ lzsawrap_error_t lzsa_decompress_v1_backward(
         return LZSAWRAP_ERR_DECOMPRESSION_FAILED;
     }
 
+    /* Never report more output than the buffer holds */
+    if (result > *output_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
     *output_size = result;
     return LZSAWRAP_OK;
 }
@@ -324,6 +452,10 @@ lzsawrap_error_t lzsa_decompress_v2_backward(
         return LZSAWRAP_OK;
     }
 
+    if (!lzsa_version_is_supported(LZSA_VERSION_2)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
     /* For backward raw blocks, we need a large enough buffer */
     if (*output_size < 65536) {
         return LZSAWRAP_ERR_BUFFER_TOO_SMALL;
@@ -354,31 +486,1011 @@ lzsawrap_error_t lzsa_decompress_v2_backward(
         return LZSAWRAP_ERR_DECOMPRESSION_FAILED;
     }
 
+    /* Never report more output than the buffer holds */
+    if (result > *output_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    *output_size = result;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_decompress_raw(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t version)
+{
+    /* Validate inputs */
+    if (!input) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+
+    if (!lzsa_version_is_supported(version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    /* Make mutable copy - library modifies the buffer! */
+    unsigned char* input_copy = (unsigned char*)malloc(input_size);
+    if (!input_copy) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(input_copy, input, input_size);
+
+    /* For raw blocks the version is an input, not detected */
+    int format_version = (int)version;
+
+    size_t result = lzsa_decompress_inmem(
+        input_copy,
+        (unsigned char*)output,
+        input_size,
+        *output_size,
+        LZSA_FLAG_RAW_BLOCK,
+        &format_version
+    );
+
+    free(input_copy);
+
+    if (result == (size_t)-1) {
+        return LZSAWRAP_ERR_DECOMPRESSION_FAILED;
+    }
+
+    /* Never report more output than the buffer holds */
+    if (result > *output_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
     *output_size = result;
     return LZSAWRAP_OK;
 }
 
 /* ============================================================================
- * Utility Functions
+ * Framed Compression
  * ============================================================================ */
 
-const char* lzsa_error_string(lzsawrap_error_t error) {
-    switch (error) {
-        case LZSAWRAP_OK: return "Success";
-        case LZSAWRAP_ERR_INPUT_NULL: return "Input pointer is NULL";
-        case LZSAWRAP_ERR_OUTPUT_NULL: return "Output pointer is NULL";
-        case LZSAWRAP_ERR_OUTPUT_SIZE_NULL: return "Output size pointer is NULL";
-        case LZSAWRAP_ERR_BUFFER_TOO_SMALL: return "Output buffer too small";
-        case LZSAWRAP_ERR_COMPRESSION_FAILED: return "Compression failed";
-        case LZSAWRAP_ERR_DECOMPRESSION_FAILED: return "Decompression failed";
-        case LZSAWRAP_ERR_INVALID_FORMAT: return "Invalid or corrupted format";
-        case LZSAWRAP_ERR_INVALID_VERSION: return "Invalid version specified";
-        case LZSAWRAP_ERR_OUT_OF_MEMORY: return "Out of memory";
-        case LZSAWRAP_ERR_INVALID_MODE: return "Invalid compression mode";
-        default: return "Unknown error";
+static int lzsa_compressor_init_for(lzsa_compressor* compressor,
+                                    const lzsa_options_t* options) {
+    return lzsa_compressor_init(compressor, BLOCK_SIZE * 2,
+                                lzsa_options_to_min_match(options),
+                                (int)options->version,
+                                (int)lzsa_options_to_flags(options));
+}
+
+/*
+ * Compress window[previous_size .. previous_size + input_size] into a framed
+ * stream, one block at a time. The first previous_size bytes (at most one
+ * block) are history that the first block may reference. The window is
+ * handed to the compressor, so it must be a private copy. Options must have
+ * been validated by the caller.
+ *
+ * The compressor is left initialized, so it may compress further streams.
+ */
+static lzsawrap_error_t lzsa_compress_framed_with(
+    lzsa_compressor* compressor,
+    unsigned char* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options,
+    lzsa_stats_t* stats)
+{
+    lzsawrap_error_t error = LZSAWRAP_OK;
+    int frame_size = lzsa_get_frame_size();
+    size_t out_pos = 0;
+    size_t block_count = 0;
+    size_t command_count = 0;
+    size_t match_count = 0;
+    size_t literal_count = 0;
+
+    int header_size = lzsa_encode_header((unsigned char*)output, (int)*output_size,
+                                         (int)options->version);
+    if (header_size < 0) {
+        error = LZSAWRAP_ERR_COMPRESSION_FAILED;
+    } else {
+        out_pos = (size_t)header_size;
+    }
+
+    size_t in_pos = previous_size;
+    size_t window_size = previous_size + input_size;
+    size_t max_block = (size_t)options->block_size;
+
+    while (error == LZSAWRAP_OK && in_pos < window_size) {
+        size_t block_in = window_size - in_pos;
+        if (block_in > max_block) block_in = max_block;
+
+        /* Room for this block's data, keeping its frame and the footer free */
+        if (*output_size - out_pos < 2 * (size_t)frame_size) {
+            error = LZSAWRAP_ERR_COMPRESSION_FAILED;
+            break;
+        }
+        size_t room = *output_size - out_pos - 2 * (size_t)frame_size;
+
+        /* A compressed block must be smaller than its input, otherwise the
+         * stored fallback below is used, as in lzsa_compress_inmem */
+        size_t max_block_out = block_in - 1;
+        if (max_block_out > room) max_block_out = room;
+
+        /* The command count and the compressor's stats accumulate over its
+         * lifetime, and a block that does not fit still adds to them */
+        int commands_before = lzsa_compressor_get_command_count(compressor);
+        int matches_before = compressor->stats.match_divisor;
+        int literals_before = compressor->stats.total_literals;
+
+        int block_size = lzsa_compressor_shrink_block(
+            compressor,
+            window + in_pos - previous_size,
+            (int)previous_size,
+            (int)block_in,
+            (unsigned char*)output + out_pos + frame_size,
+            (int)max_block_out
+        );
+
+        int frame_header_size;
+        if (block_size >= 0) {
+            frame_header_size = lzsa_encode_compressed_block_frame(
+                (unsigned char*)output + out_pos, frame_size, block_size);
+            command_count += (size_t)(lzsa_compressor_get_command_count(compressor) - commands_before);
+            match_count += (size_t)(compressor->stats.match_divisor - matches_before);
+            literal_count += (size_t)(compressor->stats.total_literals - literals_before);
+        } else if (block_in <= room) {
+            /* Incompressible: store the block as-is */
+            block_size = (int)block_in;
+            frame_header_size = lzsa_encode_uncompressed_block_frame(
+                (unsigned char*)output + out_pos, frame_size, block_size);
+            memcpy(output + out_pos + frame_size, window + in_pos, block_in);
+        } else {
+            frame_header_size = -1;
+        }
+
+        if (frame_header_size < 0) {
+            error = LZSAWRAP_ERR_COMPRESSION_FAILED;
+            break;
+        }
+
+        out_pos += (size_t)frame_header_size + (size_t)block_size;
+        in_pos += block_in;
+        previous_size = block_in;
+        block_count++;
+    }
+
+    if (error == LZSAWRAP_OK) {
+        int footer_size = lzsa_encode_footer_frame((unsigned char*)output + out_pos,
+                                                   (int)(*output_size - out_pos));
+        if (footer_size < 0) {
+            error = LZSAWRAP_ERR_COMPRESSION_FAILED;
+        } else {
+            out_pos += (size_t)footer_size;
+        }
+    }
+
+    if (error == LZSAWRAP_OK) {
+        *output_size = out_pos;
+
+        if (stats) {
+            stats->input_size = input_size;
+            stats->output_size = out_pos;
+            stats->block_count = block_count;
+            stats->command_count = command_count;
+            stats->match_count = match_count;
+            stats->literal_count = literal_count;
+        }
     }
+
+    return error;
 }
 
-const char* lzsa_version_string(void) {
-    return "LZSA 1.4.1 (wrapper)";
+/* lzsa_compress_framed_with on a compressor created for this call */
+static lzsawrap_error_t lzsa_compress_framed(
+    unsigned char* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options,
+    lzsa_stats_t* stats)
+{
+    lzsa_compressor compressor;
+    if (lzsa_compressor_init_for(&compressor, options) != 0) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+
+    lzsawrap_error_t error = lzsa_compress_framed_with(&compressor, window, previous_size,
+                                                       input_size, output, output_size,
+                                                       options, stats);
+
+    lzsa_compressor_destroy(&compressor);
+    return error;
+}
+
+/* ============================================================================
+ * Statistics API
+ * ============================================================================ */
+
+lzsawrap_error_t lzsa_compress_with_stats(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options,
+    lzsa_stats_t* stats)
+{
+    /* Validate inputs */
+    if (!input) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output || !stats) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+    if (!options) return LZSAWRAP_ERR_INVALID_MODE;
+
+    if (!lzsa_version_is_supported(options->version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (!lzsa_min_match_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_MIN_MATCH;
+    }
+
+    if (!lzsa_block_size_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    /* Raw blocks are compressed by lzsa_compress_inmem, which keeps no stats */
+    if (options->mode != LZSA_MODE_NORMAL) {
+        return LZSAWRAP_ERR_INVALID_MODE;
+    }
+
+    memset(stats, 0, sizeof(*stats));
+
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    /* Same precaution as lzsa_compress: never hand the caller's buffer over */
+    unsigned char* window = (unsigned char*)malloc(input_size);
+    if (!window) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(window, input, input_size);
+
+    lzsawrap_error_t error = lzsa_compress_framed(window, 0, input_size,
+                                                  output, output_size, options, stats);
+
+    free(window);
+    return error;
+}
+
+/* ============================================================================
+ * Dictionary API
+ * ============================================================================ */
+
+lzsawrap_error_t lzsa_compress_with_dictionary(
+    const uint8_t* input,
+    size_t input_size,
+    const uint8_t* dictionary,
+    size_t dictionary_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options)
+{
+    if (dictionary_size == 0) {
+        return lzsa_compress(input, input_size, output, output_size, options);
+    }
+
+    /* Validate inputs */
+    if (!input || !dictionary) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+    if (!options) return LZSAWRAP_ERR_INVALID_MODE;
+
+    if (!lzsa_version_is_supported(options->version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (!lzsa_min_match_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_MIN_MATCH;
+    }
+
+    if (!lzsa_block_size_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    /* Raw blocks have no frames to carry the history across */
+    if (options->mode != LZSA_MODE_NORMAL) {
+        return LZSAWRAP_ERR_INVALID_MODE;
+    }
+
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    /* Matches never reach further back than one block */
+    if (dictionary_size > BLOCK_SIZE) {
+        dictionary += dictionary_size - BLOCK_SIZE;
+        dictionary_size = BLOCK_SIZE;
+    }
+
+    /* Lay out the dictionary right before the input, as the previous block */
+    unsigned char* window = (unsigned char*)malloc(dictionary_size + input_size);
+    if (!window) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(window, dictionary, dictionary_size);
+    memcpy(window + dictionary_size, input, input_size);
+
+    lzsawrap_error_t error = lzsa_compress_framed(window, dictionary_size, input_size,
+                                                  output, output_size, options, NULL);
+
+    free(window);
+    return error;
+}
+
+lzsawrap_error_t lzsa_decompress_with_dictionary(
+    const uint8_t* input,
+    size_t input_size,
+    const uint8_t* dictionary,
+    size_t dictionary_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t* detected_version)
+{
+    if (dictionary_size == 0) {
+        return lzsa_decompress(input, input_size, output, output_size, detected_version);
+    }
+
+    /* Validate inputs */
+    if (!input || !dictionary) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+
+    if (input_size == 0) {
+        *output_size = 0;
+        if (detected_version) {
+            *detected_version = LZSA_VERSION_1;
+        }
+        return LZSAWRAP_OK;
+    }
+
+    /* Only the last block's worth of dictionary was visible to the compressor */
+    if (dictionary_size > BLOCK_SIZE) {
+        dictionary += dictionary_size - BLOCK_SIZE;
+        dictionary_size = BLOCK_SIZE;
+    }
+
+    int header_size = lzsa_get_header_size();
+    int frame_size = lzsa_get_frame_size();
+    int format_version = 0;
+
+    if (input_size < (size_t)header_size ||
+        lzsa_decode_header((const unsigned char*)input, header_size, &format_version) != 0) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    if (!lzsa_version_is_supported((lzsa_version_t)format_version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    /* Decode after a copy of the dictionary so matches can reach into it */
+    size_t window_size = dictionary_size + *output_size;
+    unsigned char* window = (unsigned char*)malloc(window_size);
+    if (!window) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(window, dictionary, dictionary_size);
+
+    /* Make a mutable copy for the library, as lzsa_decompress does */
+    unsigned char* input_copy = (unsigned char*)malloc(input_size);
+    if (!input_copy) {
+        free(window);
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(input_copy, input, input_size);
+
+    lzsawrap_error_t error = LZSAWRAP_OK;
+    size_t in_pos = (size_t)header_size;
+    size_t out_pos = dictionary_size;
+
+    for (;;) {
+        unsigned int block_size = 0;
+        int is_uncompressed = 0;
+
+        if (input_size - in_pos < (size_t)frame_size ||
+            lzsa_decode_frame(input_copy + in_pos, frame_size, &block_size, &is_uncompressed) != 0) {
+            error = LZSAWRAP_ERR_DECOMPRESSION_FAILED;
+            break;
+        }
+        in_pos += (size_t)frame_size;
+
+        /* Footer */
+        if (block_size == 0) {
+            break;
+        }
+
+        if (input_size - in_pos < block_size) {
+            error = LZSAWRAP_ERR_DECOMPRESSION_FAILED;
+            break;
+        }
+
+        if (is_uncompressed) {
+            if (window_size - out_pos < block_size) {
+                error = LZSAWRAP_ERR_DECOMPRESSION_FAILED;
+                break;
+            }
+            memcpy(window + out_pos, input_copy + in_pos, block_size);
+            out_pos += block_size;
+        } else {
+            /* The size limit counts from window + out_pos; a block never
+             * decodes to more than BLOCK_SIZE */
+            size_t room = window_size - out_pos;
+            if (room > BLOCK_SIZE) {
+                room = BLOCK_SIZE;
+            }
+            int result = lzsa_decompressor_expand_block(
+                input_copy + in_pos,
+                (int)block_size,
+                window,
+                (int)out_pos,
+                (int)room,
+                format_version,
+                0 /* flags - normal decompression */
+            );
+
+            if (result < 0) {
+                error = LZSAWRAP_ERR_DECOMPRESSION_FAILED;
+                break;
+            }
+            /* Cannot happen when the decoder honors its limit */
+            if ((size_t)result > room) {
+                error = LZSAWRAP_ERR_INVALID_FORMAT;
+                break;
+            }
+            out_pos += (size_t)result;
+        }
+
+        in_pos += block_size;
+    }
+
+    free(input_copy);
+
+    if (error == LZSAWRAP_OK) {
+        *output_size = out_pos - dictionary_size;
+        memcpy(output, window + dictionary_size, *output_size);
+
+        if (detected_version) {
+            *detected_version = (format_version == 1) ? LZSA_VERSION_1 : LZSA_VERSION_2;
+        }
+    }
+
+    free(window);
+    return error;
+}
+
+/* ============================================================================
+ * Block API
+ * ============================================================================ */
+
+lzsawrap_error_t lzsa_encode_stream_header(
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t version)
+{
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+
+    if (!lzsa_version_is_supported(version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    int header_size = lzsa_encode_header(
+        (unsigned char*)output,
+        (int)*output_size,
+        (int)version
+    );
+
+    if (header_size < 0) {
+        return LZSAWRAP_ERR_BUFFER_TOO_SMALL;
+    }
+
+    *output_size = (size_t)header_size;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_encode_stream_footer(
+    uint8_t* output,
+    size_t* output_size)
+{
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+
+    int footer_size = lzsa_encode_footer_frame((unsigned char*)output, (int)*output_size);
+
+    if (footer_size < 0) {
+        return LZSAWRAP_ERR_BUFFER_TOO_SMALL;
+    }
+
+    *output_size = (size_t)footer_size;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_compress_block(
+    const uint8_t* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options)
+{
+    /* Validate inputs */
+    if (!window) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+    if (!options) return LZSAWRAP_ERR_INVALID_MODE;
+
+    if (!lzsa_version_is_supported(options->version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (!lzsa_min_match_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_MIN_MATCH;
+    }
+
+    /* Blocks only exist inside a framed stream */
+    if (options->mode != LZSA_MODE_NORMAL) {
+        return LZSAWRAP_ERR_INVALID_MODE;
+    }
+
+    if (previous_size > BLOCK_SIZE || input_size > BLOCK_SIZE) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    /* A zero-sized block frame is the stream footer, so emit nothing */
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    /* Room for the uncompressed fallback is always required */
+    int frame_size = lzsa_get_frame_size();
+    if (*output_size < (size_t)frame_size + input_size) {
+        return LZSAWRAP_ERR_BUFFER_TOO_SMALL;
+    }
+
+    /* Same precaution as lzsa_compress: never hand the caller's buffer over */
+    size_t window_size = previous_size + input_size;
+    unsigned char* window_copy = (unsigned char*)malloc(window_size);
+    if (!window_copy) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(window_copy, window, window_size);
+
+    lzsa_compressor compressor;
+    if (lzsa_compressor_init_for(&compressor, options) != 0) {
+        free(window_copy);
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+
+    /* A compressed block must be smaller than its input, otherwise it is
+     * stored below, as in the framed block loop; the room check above
+     * guarantees the space */
+    size_t max_block_out = input_size - 1;
+
+    int block_size = lzsa_compressor_shrink_block(
+        &compressor,
+        window_copy,
+        (int)previous_size,
+        (int)input_size,
+        (unsigned char*)output + frame_size,
+        (int)max_block_out
+    );
+
+    lzsa_compressor_destroy(&compressor);
+
+    int header_size;
+    if (block_size >= 0) {
+        header_size = lzsa_encode_compressed_block_frame(
+            (unsigned char*)output, frame_size, block_size);
+    } else {
+        /* Incompressible: store the block as-is */
+        block_size = (int)input_size;
+        header_size = lzsa_encode_uncompressed_block_frame(
+            (unsigned char*)output, frame_size, block_size);
+        memcpy(output + frame_size, window_copy + previous_size, input_size);
+    }
+
+    free(window_copy);
+
+    if (header_size < 0) {
+        return LZSAWRAP_ERR_COMPRESSION_FAILED;
+    }
+
+    *output_size = (size_t)header_size + (size_t)block_size;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_compress_block_data(
+    const uint8_t* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options)
+{
+    /* Validate inputs */
+    if (!window) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+    if (!options) return LZSAWRAP_ERR_INVALID_MODE;
+
+    if (!lzsa_version_is_supported(options->version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (!lzsa_min_match_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_MIN_MATCH;
+    }
+
+    if (previous_size > BLOCK_SIZE || input_size > BLOCK_SIZE) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    /* Same precaution as lzsa_compress: never hand the caller's buffer over */
+    size_t window_size = previous_size + input_size;
+    unsigned char* window_copy = (unsigned char*)malloc(window_size);
+    if (!window_copy) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(window_copy, window, window_size);
+
+    /* Only the block flags of a framed stream: no end-of-data marker */
+    lzsa_options_t block_options = *options;
+    block_options.mode = LZSA_MODE_NORMAL;
+
+    lzsa_compressor compressor;
+    if (lzsa_compressor_init_for(&compressor, &block_options) != 0) {
+        free(window_copy);
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+
+    size_t max_block_out = *output_size;
+    if (max_block_out > INT_MAX) max_block_out = INT_MAX;
+
+    int block_size = lzsa_compressor_shrink_block(
+        &compressor,
+        window_copy,
+        (int)previous_size,
+        (int)input_size,
+        (unsigned char*)output,
+        (int)max_block_out
+    );
+
+    lzsa_compressor_destroy(&compressor);
+    free(window_copy);
+
+    if (block_size < 0) {
+        return LZSAWRAP_ERR_COMPRESSION_FAILED;
+    }
+
+    *output_size = (size_t)block_size;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_decode_stream_header(
+    const uint8_t* input,
+    size_t input_size,
+    lzsa_version_t* version)
+{
+    if (!input) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!version) return LZSAWRAP_ERR_OUTPUT_NULL;
+
+    int header_size = lzsa_get_header_size();
+    if (input_size < (size_t)header_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    int format_version = 0;
+    if (lzsa_decode_header((const unsigned char*)input, header_size, &format_version) != 0) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    *version = (format_version == 1) ? LZSA_VERSION_1 : LZSA_VERSION_2;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_decode_block_frame(
+    const uint8_t* input,
+    size_t input_size,
+    size_t* block_size,
+    int* is_uncompressed)
+{
+    if (!input) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!block_size || !is_uncompressed) return LZSAWRAP_ERR_OUTPUT_NULL;
+
+    int frame_size = lzsa_get_frame_size();
+    if (input_size < (size_t)frame_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    unsigned int size = 0;
+    int uncompressed = 0;
+    if (lzsa_decode_frame((const unsigned char*)input, frame_size, &size, &uncompressed) != 0) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    *block_size = (size_t)size;
+    *is_uncompressed = uncompressed;
+    return LZSAWRAP_OK;
+}
+
+lzsawrap_error_t lzsa_decompress_block(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* window,
+    size_t previous_size,
+    size_t* output_size,
+    lzsa_version_t version)
+{
+    /* Validate inputs */
+    if (!input) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!window) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+
+    if (!lzsa_version_is_supported(version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (previous_size > BLOCK_SIZE || input_size > BLOCK_SIZE) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    /* Make a mutable copy for the library, as lzsa_decompress does */
+    unsigned char* input_copy = (unsigned char*)malloc(input_size);
+    if (!input_copy) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(input_copy, input, input_size);
+
+    /* The size limit counts from window + previous_size, not from window.
+     * A block never decodes to more than BLOCK_SIZE, which also keeps the
+     * limit in range of an int */
+    size_t max_size = *output_size < BLOCK_SIZE ? *output_size : BLOCK_SIZE;
+    int result = lzsa_decompressor_expand_block(
+        input_copy,
+        (int)input_size,
+        (unsigned char*)window,
+        (int)previous_size,
+        (int)max_size,
+        (int)version,
+        0 /* flags - normal decompression */
+    );
+
+    free(input_copy);
+
+    if (result < 0) {
+        return LZSAWRAP_ERR_DECOMPRESSION_FAILED;
+    }
+    if ((size_t)result > *output_size) {
+        return LZSAWRAP_ERR_INVALID_FORMAT;
+    }
+
+    *output_size = (size_t)result;
+    return LZSAWRAP_OK;
+}
+
+/* ============================================================================
+ * Context API
+ * ============================================================================ */
+
+struct lzsa_context {
+    lzsa_options_t options;
+    lzsa_compressor compressor;
+    unsigned char* scratch;   /* Mutable input copy, grown as needed */
+    size_t scratch_size;
+};
+
+/* Get a scratch buffer of at least size bytes, or NULL if it cannot grow */
+static unsigned char* lzsa_context_scratch(lzsa_context_t* context, size_t size) {
+    if (size > context->scratch_size) {
+        /* The old contents are not needed, so skip realloc's copy */
+        free(context->scratch);
+        context->scratch = (unsigned char*)malloc(size);
+        context->scratch_size = context->scratch ? size : 0;
+    }
+    return context->scratch;
+}
+
+lzsawrap_error_t lzsa_context_create(
+    const lzsa_options_t* options,
+    lzsa_context_t** context)
+{
+    if (!context) return LZSAWRAP_ERR_OUTPUT_NULL;
+    *context = NULL;
+    if (!options) return LZSAWRAP_ERR_INVALID_MODE;
+
+    if (!lzsa_version_is_supported(options->version)) {
+        return LZSAWRAP_ERR_INVALID_VERSION;
+    }
+
+    if (!lzsa_min_match_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_MIN_MATCH;
+    }
+
+    if (!lzsa_block_size_is_valid(options)) {
+        return LZSAWRAP_ERR_INVALID_BLOCK_SIZE;
+    }
+
+    /* The compressor is only driven through the framed block loop */
+    if (options->mode != LZSA_MODE_NORMAL) {
+        return LZSAWRAP_ERR_INVALID_MODE;
+    }
+
+    lzsa_context_t* created = (lzsa_context_t*)malloc(sizeof(lzsa_context_t));
+    if (!created) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+
+    created->options = *options;
+    created->scratch = NULL;
+    created->scratch_size = 0;
+
+    if (lzsa_compressor_init_for(&created->compressor, options) != 0) {
+        free(created);
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+
+    *context = created;
+    return LZSAWRAP_OK;
+}
+
+void lzsa_context_destroy(lzsa_context_t* context) {
+    if (!context) return;
+
+    lzsa_compressor_destroy(&context->compressor);
+    free(context->scratch);
+    free(context);
+}
+
+lzsawrap_error_t lzsa_context_compress(
+    lzsa_context_t* context,
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size)
+{
+    if (!context) return LZSAWRAP_ERR_INVALID_MODE;
+    if (!input) return LZSAWRAP_ERR_INPUT_NULL;
+    if (!output) return LZSAWRAP_ERR_OUTPUT_NULL;
+    if (!output_size) return LZSAWRAP_ERR_OUTPUT_SIZE_NULL;
+
+    /* Same as lzsa_compress */
+    if (input_size == 0) {
+        *output_size = 0;
+        return LZSAWRAP_OK;
+    }
+
+    unsigned char* window = lzsa_context_scratch(context, input_size);
+    if (!window) {
+        return LZSAWRAP_ERR_OUT_OF_MEMORY;
+    }
+    memcpy(window, input, input_size);
+
+    return lzsa_compress_framed_with(&context->compressor, window, 0, input_size,
+                                     output, output_size, &context->options, NULL);
+}
+
+lzsawrap_error_t lzsa_context_decompress(
+    lzsa_context_t* context,
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t* detected_version)
+{
+    if (!context) return LZSAWRAP_ERR_INVALID_MODE;
+
+    /* Empty input is handled before the copy would be made */
+    unsigned char* scratch = NULL;
+    if (input && input_size > 0) {
+        scratch = lzsa_context_scratch(context, input_size);
+        if (!scratch) {
+            return LZSAWRAP_ERR_OUT_OF_MEMORY;
+        }
+    }
+
+    return lzsa_decompress_using(input, input_size, output, output_size,
+                                 detected_version, scratch);
+}
+
+/* ============================================================================
+ * Utility Functions
+ * ============================================================================ */
+
+const char* lzsa_error_string(lzsawrap_error_t error) {
+    switch (error) {
+        case LZSAWRAP_OK: return "Success";
+        case LZSAWRAP_ERR_INPUT_NULL: return "Input pointer is NULL";
+        case LZSAWRAP_ERR_OUTPUT_NULL: return "Output pointer is NULL";
+        case LZSAWRAP_ERR_OUTPUT_SIZE_NULL: return "Output size pointer is NULL";
+        case LZSAWRAP_ERR_BUFFER_TOO_SMALL: return "Output buffer too small";
+        case LZSAWRAP_ERR_COMPRESSION_FAILED: return "Compression failed";
+        case LZSAWRAP_ERR_DECOMPRESSION_FAILED: return "Decompression failed";
+        case LZSAWRAP_ERR_INVALID_FORMAT: return "Invalid or corrupted format";
+        case LZSAWRAP_ERR_INVALID_VERSION: return "Invalid version specified";
+        case LZSAWRAP_ERR_OUT_OF_MEMORY: return "Out of memory";
+        case LZSAWRAP_ERR_INVALID_MODE: return "Invalid compression mode";
+        case LZSAWRAP_ERR_INVALID_BLOCK_SIZE: return "Invalid block size";
+        case LZSAWRAP_ERR_INVALID_MIN_MATCH: return "Minimum match size out of range for version";
+        default: return "Unknown error";
+    }
+}
+
+const char* lzsa_version_string(void) {
+    return "LZSA 1.4.1 (wrapper)";
+}
+
+/* Copy up to count field offsets and report the alignment of a struct */
+#define LZSA_REPORT_LAYOUT(type, offsets, count, align, ...) do { \
+    const size_t fields[] = { __VA_ARGS__ }; \
+    size_t i; \
+    for (i = 0; offsets && i < count && i < sizeof(fields) / sizeof(fields[0]); i++) { \
+        offsets[i] = fields[i]; \
+    } \
+    if (align) { \
+        struct aligned { char c; type value; }; \
+        *align = offsetof(struct aligned, value); \
+    } \
+} while (0)
+
+size_t lzsa_options_layout(size_t* offsets, size_t count, size_t* align) {
+    LZSA_REPORT_LAYOUT(lzsa_options_t, offsets, count, align,
+        offsetof(lzsa_options_t, version),
+        offsetof(lzsa_options_t, mode),
+        offsetof(lzsa_options_t, quality),
+        offsetof(lzsa_options_t, min_match_size),
+        offsetof(lzsa_options_t, effort),
+        offsetof(lzsa_options_t, block_size));
+    return sizeof(lzsa_options_t);
+}
+
+size_t lzsa_stats_layout(size_t* offsets, size_t count, size_t* align) {
+    LZSA_REPORT_LAYOUT(lzsa_stats_t, offsets, count, align,
+        offsetof(lzsa_stats_t, input_size),
+        offsetof(lzsa_stats_t, output_size),
+        offsetof(lzsa_stats_t, block_count),
+        offsetof(lzsa_stats_t, command_count),
+        offsetof(lzsa_stats_t, match_count),
+        offsetof(lzsa_stats_t, literal_count));
+    return sizeof(lzsa_stats_t);
+}
+
+#ifdef LZSA_ASAN
+/* ============================================================================
+ * AddressSanitizer defaults (asan feature)
+ * ============================================================================ */
+
+/* Read by the runtime at startup, before ASAN_OPTIONS, which can override
+ * it. The tests ask for impossibly large buffers to check that allocation
+ * failure is reported as an error, where ASan would abort by default. This
+ * lives in the wrapper because an object nothing refers to is not pulled
+ * out of the static library. */
+const char* __asan_default_options(void);
+
+const char* __asan_default_options(void) {
+    return "allocator_may_return_null=1";
 }
+#endif
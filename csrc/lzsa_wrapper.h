@@ -33,6 +33,7 @@ typedef enum {
 typedef enum {
     LZSA_MODE_NORMAL = 0,     /* Forward compression, framed output */
     LZSA_MODE_RAW_FORWARD,    /* Forward compression, raw block (no frame) */
+    LZSA_MODE_RAW_BACKWARD,   /* Backward compression, raw block (no frame) */
 } lzsa_mode_t;
 
 /**
@@ -58,6 +59,8 @@ typedef enum {
     LZSAWRAP_ERR_INVALID_VERSION = -8,
     LZSAWRAP_ERR_OUT_OF_MEMORY = -9,
     LZSAWRAP_ERR_INVALID_MODE = -10,
+    LZSAWRAP_ERR_INVALID_BLOCK_SIZE = -11,
+    LZSAWRAP_ERR_INVALID_MIN_MATCH = -12,
 } lzsawrap_error_t;
 
 /**
@@ -67,9 +70,38 @@ typedef struct {
     lzsa_version_t version;   /* Format version (1 or 2) */
     lzsa_mode_t mode;         /* Compression mode */
     lzsa_quality_t quality;   /* Quality/speed tradeoff */
-    uint32_t min_match_size;  /* Minimum match size (3-5, default 3) */
+    uint32_t min_match_size;  /* Minimum match size (LZSA1: 3-5, LZSA2: 2-5, default 3) */
+    uint32_t effort;          /* Effort level 0-9, or LZSA_EFFORT_FROM_QUALITY (default) */
+    uint32_t block_size;      /* Input bytes per frame in normal mode (1-65536, default 65536) */
 } lzsa_options_t;
 
+/**
+ * Effort value that defers to the quality setting (speed = 0, ratio = 9)
+ */
+#define LZSA_EFFORT_FROM_QUALITY 0xFFFFFFFFu
+
+/**
+ * Largest accepted min_match_size for either version
+ */
+#define LZSA_MAX_MIN_MATCH_SIZE 5
+
+/**
+ * Compression statistics
+ */
+typedef struct {
+    size_t input_size;        /* Uncompressed size */
+    size_t output_size;       /* Compressed size, including framing */
+    size_t block_count;       /* Number of block frames written */
+    size_t command_count;     /* Commands (literal run + match) in compressed blocks */
+    size_t match_count;       /* Matches in compressed blocks */
+    size_t literal_count;     /* Literal bytes in compressed blocks */
+} lzsa_stats_t;
+
+/**
+ * Reusable compression context (opaque, see lzsa_context_create)
+ */
+typedef struct lzsa_context lzsa_context_t;
+
 /* ============================================================================
  * Helper Functions
  * ============================================================================ */
@@ -114,6 +146,13 @@ size_t lzsa_get_max_decompressed_size(
 /**
  * Compress data with custom options
  *
+ * Normal mode splits the input into as many blocks of options->block_size
+ * bytes as needed (LZSAWRAP_ERR_INVALID_BLOCK_SIZE if that is not 1 to 64KB);
+ * smaller blocks need a larger output buffer than
+ * lzsa_get_max_compressed_size reports, 3 bytes per extra block. Raw modes
+ * produce a single block and return LZSAWRAP_ERR_INVALID_BLOCK_SIZE for
+ * input larger than 64KB.
+ *
  * @param input Input data to compress
  * @param input_size Size of input data
  * @param output Output buffer for compressed data
@@ -217,6 +256,339 @@ lzsawrap_error_t lzsa_decompress_v2(
     size_t* output_size
 );
 
+/**
+ * Decompress a backward LZSA1 raw block (as produced by LZSA_MODE_RAW_BACKWARD)
+ *
+ * @param input Compressed raw block
+ * @param input_size Size of compressed data
+ * @param output Output buffer (at least 65536 bytes)
+ * @param output_size Pointer to output size
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decompress_v1_backward(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size
+);
+
+/**
+ * Decompress a backward LZSA2 raw block (as produced by LZSA_MODE_RAW_BACKWARD)
+ *
+ * @param input Compressed raw block
+ * @param input_size Size of compressed data
+ * @param output Output buffer (at least 65536 bytes)
+ * @param output_size Pointer to output size
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decompress_v2_backward(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size
+);
+
+/**
+ * Decompress a forward raw block (as produced by LZSA_MODE_RAW_FORWARD)
+ *
+ * Raw blocks carry neither a version nor a size, so both must be known.
+ *
+ * @param input Compressed raw block
+ * @param input_size Size of compressed data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @param version Format version the block was compressed with
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decompress_raw(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t version
+);
+
+/* ============================================================================
+ * Statistics API
+ * ============================================================================ */
+
+/**
+ * Compress data into a framed stream and report statistics
+ *
+ * Produces the same kind of stream as lzsa_compress in normal mode. Blocks
+ * that do not compress are stored as-is and add no commands.
+ *
+ * @param input Input data to compress
+ * @param input_size Size of input data
+ * @param output Output buffer for compressed data
+ * @param output_size Pointer to output buffer size (in: max size, out: actual size)
+ * @param options Compression options (mode must be LZSA_MODE_NORMAL)
+ * @param stats Pointer to store the statistics
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_compress_with_stats(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options,
+    lzsa_stats_t* stats
+);
+
+/* ============================================================================
+ * Dictionary API
+ * ============================================================================ */
+
+/**
+ * Compress data into a framed stream, using a dictionary as match history
+ *
+ * The first block may reference the last 64 KB of the dictionary, which is
+ * not part of the output. Decompress with lzsa_decompress_with_dictionary
+ * and the same dictionary. An empty dictionary behaves like lzsa_compress.
+ *
+ * @param input Input data
+ * @param input_size Size of input data
+ * @param dictionary Dictionary data (may be NULL if dictionary_size is 0)
+ * @param dictionary_size Size of dictionary data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @param options Compression options (mode must be LZSA_MODE_NORMAL)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_compress_with_dictionary(
+    const uint8_t* input,
+    size_t input_size,
+    const uint8_t* dictionary,
+    size_t dictionary_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options
+);
+
+/**
+ * Decompress a framed stream made by lzsa_compress_with_dictionary
+ *
+ * @param input Compressed data
+ * @param input_size Size of compressed data
+ * @param dictionary Dictionary the data was compressed with
+ * @param dictionary_size Size of dictionary data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @param detected_version Pointer to store detected version (can be NULL)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decompress_with_dictionary(
+    const uint8_t* input,
+    size_t input_size,
+    const uint8_t* dictionary,
+    size_t dictionary_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t* detected_version
+);
+
+/* ============================================================================
+ * Block API (used by the streaming writer/reader)
+ * ============================================================================ */
+
+/**
+ * Write the 3-byte stream header that starts a framed LZSA stream
+ *
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: bytes written)
+ * @param version Format version
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_encode_stream_header(
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t version
+);
+
+/**
+ * Write the 3-byte footer that terminates a framed LZSA stream
+ *
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: bytes written)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_encode_stream_footer(
+    uint8_t* output,
+    size_t* output_size
+);
+
+/**
+ * Compress one block of a framed stream, including its block frame header
+ *
+ * The block to compress is window[previous_size .. previous_size + input_size].
+ * The first previous_size bytes are the previous block, which matches may
+ * reference. Incompressible blocks are stored uncompressed, so the output
+ * buffer must hold at least input_size + 3 bytes.
+ *
+ * @param window Previous block followed by the block to compress
+ * @param previous_size Size of the previous block (0 to 65536)
+ * @param input_size Size of the block to compress (0 to 65536)
+ * @param output Output buffer for the block frame
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @param options Compression options (mode must be LZSA_MODE_NORMAL)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_compress_block(
+    const uint8_t* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options
+);
+
+/**
+ * Compress one block without a frame header or an end-of-data marker
+ *
+ * Same window layout as lzsa_compress_block, but the output is only the
+ * block data, exactly as it follows a compressed block frame, and there is
+ * no uncompressed fallback: LZSAWRAP_ERR_COMPRESSION_FAILED is returned if
+ * the block does not fit in the output buffer. The mode in options is
+ * ignored.
+ *
+ * @param window Previous data followed by the block to compress
+ * @param previous_size Size of the previous data (0 to 65536)
+ * @param input_size Size of the block to compress (0 to 65536)
+ * @param output Output buffer for the block data
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @param options Compression options
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_compress_block_data(
+    const uint8_t* window,
+    size_t previous_size,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    const lzsa_options_t* options
+);
+
+/**
+ * Parse the 3-byte stream header that starts a framed LZSA stream
+ *
+ * @param input Header bytes
+ * @param input_size Number of header bytes available
+ * @param version Pointer to store the format version
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decode_stream_header(
+    const uint8_t* input,
+    size_t input_size,
+    lzsa_version_t* version
+);
+
+/**
+ * Parse a 3-byte block frame header
+ *
+ * A block size of 0 marks the stream footer.
+ *
+ * @param input Frame header bytes
+ * @param input_size Number of frame header bytes available
+ * @param block_size Pointer to store the size of the block data that follows
+ * @param is_uncompressed Pointer to store whether the block is stored as-is
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decode_block_frame(
+    const uint8_t* input,
+    size_t input_size,
+    size_t* block_size,
+    int* is_uncompressed
+);
+
+/**
+ * Decompress the data of one compressed block of a framed stream
+ *
+ * The first previous_size bytes of window must hold the previous decoded
+ * block, which matches may reference. Output is written right after it.
+ *
+ * @param input Block data (without the frame header)
+ * @param input_size Size of the block data
+ * @param window Previous block followed by room for the output
+ * @param previous_size Size of the previous block (0 to 65536)
+ * @param output_size Pointer to output size (in: room after the previous block, out: actual size)
+ * @param version Format version of the stream
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_decompress_block(
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* window,
+    size_t previous_size,
+    size_t* output_size,
+    lzsa_version_t version
+);
+
+/* ============================================================================
+ * Context API
+ * ============================================================================ */
+
+/**
+ * Create a context that keeps its compressor and scratch buffer across calls
+ *
+ * The options are validated and copied; they apply to every
+ * lzsa_context_compress call. Only LZSA_MODE_NORMAL is supported.
+ * Free the context with lzsa_context_destroy.
+ *
+ * @param options Compression options
+ * @param context Pointer to store the new context (set to NULL on failure)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_context_create(
+    const lzsa_options_t* options,
+    lzsa_context_t** context
+);
+
+/**
+ * Free a context and everything it owns (NULL is ignored)
+ *
+ * @param context Context from lzsa_context_create
+ */
+void lzsa_context_destroy(lzsa_context_t* context);
+
+/**
+ * Compress data into a framed stream with the context's options
+ *
+ * @param context Context from lzsa_context_create
+ * @param input Input data
+ * @param input_size Size of input data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_context_compress(
+    lzsa_context_t* context,
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size
+);
+
+/**
+ * Decompress a framed stream like lzsa_decompress, reusing the context's
+ * scratch buffer
+ *
+ * @param context Context from lzsa_context_create
+ * @param input Compressed data
+ * @param input_size Size of compressed data
+ * @param output Output buffer
+ * @param output_size Pointer to output size (in: max size, out: actual size)
+ * @param detected_version Pointer to store detected version (can be NULL)
+ * @return LZSAWRAP_OK on success, error code on failure
+ */
+lzsawrap_error_t lzsa_context_decompress(
+    lzsa_context_t* context,
+    const uint8_t* input,
+    size_t input_size,
+    uint8_t* output,
+    size_t* output_size,
+    lzsa_version_t* detected_version
+);
+
 /* ============================================================================
  * Utility Functions
  * ============================================================================ */
@@ -236,6 +608,25 @@ const char* lzsa_error_string(lzsawrap_error_t error);
  */
 const char* lzsa_version_string(void);
 
+/**
+ * Get the compiled layout of lzsa_options_t
+ *
+ * Lets bindings check their copy of the struct against the compiler's:
+ * the offsets of its fields, in declaration order, are written to offsets
+ * (at most count of them).
+ *
+ * @param offsets Receives the field offsets (may be NULL)
+ * @param count Number of entries offsets has room for
+ * @param align Receives the alignment of the struct (may be NULL)
+ * @return sizeof(lzsa_options_t)
+ */
+size_t lzsa_options_layout(size_t* offsets, size_t count, size_t* align);
+
+/**
+ * Get the compiled layout of lzsa_stats_t, as lzsa_options_layout does
+ */
+size_t lzsa_stats_layout(size_t* offsets, size_t count, size_t* align);
+
 #ifdef __cplusplus
 }
 #endif
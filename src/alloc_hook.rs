@@ -0,0 +1,215 @@
+//! Routing the C library's heap allocations through Rust
+//!
+//! The bundled C code (the wrapper, LZSA and libdivsufsort) normally calls
+//! `malloc` and `free` itself. With the `alloc-hook` feature, the build
+//! force-includes `csrc/lzsa_alloc.h`, which redirects `malloc`, `calloc`,
+//! `realloc` and `free` to functions defined here. By default they use the
+//! Rust global allocator, so a `#[global_allocator]` sees every C
+//! allocation; [`set_allocator`] installs other functions instead.
+//!
+//! This is compile-time redirection, so it only reaches C code that this
+//! crate builds. With `system-lib` the prebuilt library keeps calling the
+//! system allocator and only the wrapper's own allocations are covered.
+//!
+//! Each allocation records the allocator that made it, so installing a new
+//! one while C allocations are live is fine: they are still freed by the
+//! allocator they came from.
+
+use core::alloc::Layout;
+use core::ffi::c_void;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Allocation functions for the C code, installed with [`set_allocator`]
+///
+/// They follow the contract of [`GlobalAlloc`](core::alloc::GlobalAlloc):
+/// `alloc` returns memory for the layout or null, and `dealloc` receives
+/// pointers from `alloc` with the layout they were made with.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocator {
+    /// Allocate memory for a layout of non-zero size, or return null
+    pub alloc: unsafe fn(Layout) -> *mut u8,
+    /// Free memory returned by `alloc` with the same layout
+    pub dealloc: unsafe fn(*mut u8, Layout),
+}
+
+impl Allocator {
+    /// The Rust global allocator (the default)
+    pub const GLOBAL: Self = Self {
+        alloc: alloc::alloc::alloc,
+        dealloc: alloc::alloc::dealloc,
+    };
+}
+
+static GLOBAL: Allocator = Allocator::GLOBAL;
+
+static CURRENT: AtomicPtr<Allocator> = AtomicPtr::new(&GLOBAL as *const _ as *mut _);
+
+/// Use `allocator` for C allocations made from now on
+///
+/// Allocations that are already live are still freed by the allocator that
+/// made them.
+///
+/// # Safety
+///
+/// The functions must uphold the contract described on [`Allocator`]. They
+/// are called from every thread that uses the crate, possibly at once.
+pub unsafe fn set_allocator(allocator: &'static Allocator) {
+    CURRENT.store(allocator as *const _ as *mut _, Ordering::Release);
+}
+
+/// Bookkeeping stored in front of every allocation handed to C
+#[repr(C)]
+struct Header {
+    size: usize,
+    allocator: *const Allocator,
+}
+
+/// Alignment of every allocation, enough for any C type (`max_align_t`)
+const ALIGN: usize = 16;
+
+/// Space taken by the header, keeping the data after it aligned
+const HEADER_SIZE: usize = if size_of::<Header>() > ALIGN {
+    size_of::<Header>().next_multiple_of(ALIGN)
+} else {
+    ALIGN
+};
+
+fn layout_for(size: usize) -> Option<Layout> {
+    Layout::from_size_align(size.checked_add(HEADER_SIZE)?, ALIGN).ok()
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn lzsa_alloc_malloc(size: usize) -> *mut c_void {
+    let Some(layout) = layout_for(size) else {
+        return ptr::null_mut();
+    };
+
+    unsafe {
+        let allocator = &*CURRENT.load(Ordering::Acquire);
+        let base = (allocator.alloc)(layout);
+        if base.is_null() {
+            return ptr::null_mut();
+        }
+
+        base.cast::<Header>().write(Header { size, allocator });
+        base.add(HEADER_SIZE).cast()
+    }
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn lzsa_alloc_calloc(count: usize, size: usize) -> *mut c_void {
+    let Some(total) = count.checked_mul(size) else {
+        return ptr::null_mut();
+    };
+
+    unsafe {
+        let data = lzsa_alloc_malloc(total);
+        if !data.is_null() {
+            data.cast::<u8>().write_bytes(0, total);
+        }
+        data
+    }
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn lzsa_alloc_realloc(data: *mut c_void, size: usize) -> *mut c_void {
+    unsafe {
+        if data.is_null() {
+            return lzsa_alloc_malloc(size);
+        }
+
+        let old_size = data
+            .cast::<u8>()
+            .sub(HEADER_SIZE)
+            .cast::<Header>()
+            .read()
+            .size;
+        let resized = lzsa_alloc_malloc(size);
+        if !resized.is_null() {
+            ptr::copy_nonoverlapping(data.cast::<u8>(), resized.cast(), old_size.min(size));
+            lzsa_alloc_free(data);
+        }
+        resized
+    }
+}
+
+#[unsafe(no_mangle)]
+unsafe extern "C" fn lzsa_alloc_free(data: *mut c_void) {
+    if data.is_null() {
+        return;
+    }
+
+    unsafe {
+        let base = data.cast::<u8>().sub(HEADER_SIZE);
+        let header = base.cast::<Header>().read();
+        // The layout was valid when the block was allocated
+        let layout = Layout::from_size_align_unchecked(header.size + HEADER_SIZE, ALIGN);
+        ((*header.allocator).dealloc)(base, layout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Options, compress_with_options, decompress};
+    use core::cell::Cell;
+
+    std::thread_local! {
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+        static TOTAL: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe fn counting_alloc(layout: Layout) -> *mut u8 {
+        LIVE.with(|live| live.set(live.get() + 1));
+        TOTAL.with(|total| total.set(total.get() + 1));
+        unsafe { alloc::alloc::alloc(layout) }
+    }
+
+    unsafe fn counting_dealloc(ptr: *mut u8, layout: Layout) {
+        LIVE.with(|live| live.set(live.get() - 1));
+        unsafe { alloc::alloc::dealloc(ptr, layout) }
+    }
+
+    static COUNTING: Allocator = Allocator {
+        alloc: counting_alloc,
+        dealloc: counting_dealloc,
+    };
+
+    #[test]
+    fn test_counting_allocator() {
+        unsafe { set_allocator(&COUNTING) };
+
+        let data = b"Tracked allocations, tracked allocations. ".repeat(100);
+        let compressed = compress_with_options(&data, &Options::default()).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), data);
+
+        unsafe { set_allocator(&Allocator::GLOBAL) };
+
+        // Counters are per thread, and the C calls ran on this one
+        assert!(TOTAL.with(Cell::get) > 0);
+        assert_eq!(LIVE.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn test_shims() {
+        unsafe {
+            let data = lzsa_alloc_calloc(4, 25).cast::<u8>();
+            assert!(!data.is_null());
+            assert_eq!(data as usize % ALIGN, 0);
+            assert!((0..100).all(|i| *data.add(i) == 0));
+
+            data.write_bytes(0xab, 100);
+            let data = lzsa_alloc_realloc(data.cast(), 1000).cast::<u8>();
+            assert!((0..100).all(|i| *data.add(i) == 0xab));
+
+            let data = lzsa_alloc_realloc(data.cast(), 10).cast::<u8>();
+            assert!((0..10).all(|i| *data.add(i) == 0xab));
+            lzsa_alloc_free(data.cast());
+
+            lzsa_alloc_free(ptr::null_mut());
+            assert!(lzsa_alloc_calloc(usize::MAX, 2).is_null());
+            assert!(lzsa_alloc_malloc(usize::MAX).is_null());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Throughput measurement for comparing LZSA with other codecs
+//!
+//! [`benchmark`] times compression and decompression with
+//! [`std::time::Instant`], so it needs no benchmarking framework.
+//!
+//! ```rust
+//! use lzsa_sys::Options;
+//! use lzsa_sys::bench::benchmark;
+//!
+//! let data: Vec<u8> = (0..100_000u32).map(|i| (i % 97) as u8).collect();
+//! let result = benchmark(&data, &Options::default(), 5)?;
+//! println!(
+//!     "compress {:.1} MB/s, decompress {:.1} MB/s, ratio {:.3}",
+//!     result.compress_mb_per_sec, result.decompress_mb_per_sec, result.ratio
+//! );
+//! # Ok::<(), lzsa_sys::Error>(())
+//! ```
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use crate::{Error, Options, Result, compress_with_options, decompress_matching};
+
+/// Measurements from [`benchmark`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchResult {
+    /// Median compression throughput, in MB (10^6 bytes) of input per second
+    pub compress_mb_per_sec: f64,
+    /// Median decompression throughput, in MB of decompressed output per second
+    pub decompress_mb_per_sec: f64,
+    /// Compressed size divided by input size (1.0 for empty input)
+    pub ratio: f64,
+    /// Size of the compressed output in bytes
+    pub compressed_len: usize,
+}
+
+/// Compress and decompress `input` `iterations` times and report the median
+/// throughput of each
+///
+/// The first round trip is checked against `input` and returns
+/// `Error::DecompressionFailed` if it differs. An `iterations` of 0 runs
+/// once. Compression errors from `options` are returned as-is.
+pub fn benchmark(input: &[u8], options: &Options, iterations: usize) -> Result<BenchResult> {
+    let iterations = iterations.max(1);
+    let mut compress_times = Vec::with_capacity(iterations);
+    let mut decompress_times = Vec::with_capacity(iterations);
+    let mut compressed_len = 0;
+
+    for i in 0..iterations {
+        let start = Instant::now();
+        let compressed = black_box(compress_with_options(black_box(input), options)?);
+        compress_times.push(start.elapsed());
+
+        let start = Instant::now();
+        let decompressed = black_box(decompress_matching(
+            black_box(&compressed),
+            input.len(),
+            options,
+        )?);
+        decompress_times.push(start.elapsed());
+
+        if i == 0 && decompressed != input {
+            return Err(Error::DecompressionFailed);
+        }
+        compressed_len = compressed.len();
+    }
+
+    let ratio = if input.is_empty() {
+        1.0
+    } else {
+        compressed_len as f64 / input.len() as f64
+    };
+
+    Ok(BenchResult {
+        compress_mb_per_sec: mb_per_sec(input.len(), median(&mut compress_times)),
+        decompress_mb_per_sec: mb_per_sec(input.len(), median(&mut decompress_times)),
+        ratio,
+        compressed_len,
+    })
+}
+
+fn median(times: &mut [Duration]) -> Duration {
+    times.sort_unstable();
+    times[times.len() / 2]
+}
+
+fn mb_per_sec(len: usize, time: Duration) -> f64 {
+    // Clamp so very small inputs timed at zero do not divide by zero
+    len as f64 / 1e6 / time.as_secs_f64().max(1e-9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mode;
+
+    #[test]
+    fn test_benchmark() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i % 97) as u8).collect();
+        let result = benchmark(&data, &Options::default(), 3).unwrap();
+
+        assert!(result.compress_mb_per_sec > 0.0);
+        assert!(result.decompress_mb_per_sec > 0.0);
+        assert_eq!(
+            result.compressed_len,
+            compress_with_options(&data, &Options::default())
+                .unwrap()
+                .len()
+        );
+        assert_eq!(
+            result.ratio,
+            result.compressed_len as f64 / data.len() as f64
+        );
+    }
+
+    #[test]
+    fn test_benchmark_raw_and_empty() {
+        let raw = Options {
+            mode: Mode::RawBackward,
+            ..Options::default()
+        };
+        assert!(benchmark(b"raw backward benchmark data", &raw, 2).is_ok());
+
+        let result = benchmark(&[], &Options::default(), 0).unwrap();
+        assert_eq!(result.ratio, 1.0);
+        assert_eq!(result.compress_mb_per_sec, 0.0);
+    }
+
+    #[test]
+    fn test_benchmark_invalid_options() {
+        let options = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(benchmark(b"data", &options, 1), Err(Error::InvalidMinMatch));
+    }
+}
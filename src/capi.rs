@@ -0,0 +1,426 @@
+//! C-callable entry points for embedding the crate in a C host
+//!
+//! With the `capi` feature the crate exports `lzsa_rs_*` functions with a
+//! plain C ABI, declared in `csrc/lzsa_rs.h`. They wrap the safe Rust
+//! functions, so C callers get the same input checks (truncated streams,
+//! oversized block frames, output sizes reported by the codec) as Rust
+//! callers, and return the wrapper's `LZSAWRAP_*` error codes. Errors that
+//! only exist on the Rust side have codes of their own, listed below.
+//!
+//! Build a library for the host with
+//! `cargo rustc --release --features capi --crate-type staticlib` (or
+//! `cdylib`) and include `lzsa_rs.h` next to `lzsa_wrapper.h`.
+
+use core::ffi::c_int;
+use core::slice;
+
+use crate::{
+    Error, Mode, Options, Quality, Result, Version, compress_into, decompress_into,
+    max_compressed_size_for,
+};
+
+/// `LZSA_RS_ERR_CHECKSUM_MISMATCH`, for `Error::ChecksumMismatch`
+pub const ERR_CHECKSUM_MISMATCH: c_int = -101;
+/// `LZSA_RS_ERR_OUT_OF_RANGE`, for `Error::OutOfRange`
+pub const ERR_OUT_OF_RANGE: c_int = -102;
+/// `LZSA_RS_ERR_OUTPUT_TOO_LARGE`, for `Error::OutputTooLarge`
+pub const ERR_OUTPUT_TOO_LARGE: c_int = -103;
+/// `LZSA_RS_ERR_INVALID_UTF8`, for `Error::InvalidUtf8`
+pub const ERR_INVALID_UTF8: c_int = -104;
+/// `LZSA_RS_ERR_TRUNCATED`, for `Error::Truncated`
+pub const ERR_TRUNCATED: c_int = -105;
+/// `LZSA_RS_ERR_IO`, for `Error::Io`
+pub const ERR_IO: c_int = -106;
+
+/// The C code for an error: the wrapper's code where it has one
+pub fn error_code(error: Error) -> c_int {
+    if let Some(code) = error.code() {
+        return code;
+    }
+
+    match error {
+        Error::ChecksumMismatch => ERR_CHECKSUM_MISMATCH,
+        Error::OutOfRange => ERR_OUT_OF_RANGE,
+        Error::OutputTooLarge => ERR_OUTPUT_TOO_LARGE,
+        Error::InvalidUtf8 => ERR_INVALID_UTF8,
+        Error::Truncated => ERR_TRUNCATED,
+        #[cfg(feature = "std")]
+        Error::Io(_) => ERR_IO,
+        Error::InputNull
+        | Error::OutputNull
+        | Error::OutputSizeNull
+        | Error::BufferTooSmall
+        | Error::CompressionFailed
+        | Error::DecompressionFailed
+        | Error::InvalidFormat
+        | Error::InvalidVersion
+        | Error::OutOfMemory
+        | Error::InvalidMode
+        | Error::InvalidBlockSize
+        | Error::InvalidMinMatch
+        | Error::Unknown(_) => unreachable!("wrapper errors have a code"),
+    }
+}
+
+/// `lzsa_options_t` as C hands it over: the enums are plain integers that
+/// may hold any value, so they are checked before becoming [`Options`]
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct RawOptions {
+    pub version: c_int,
+    pub mode: c_int,
+    pub quality: c_int,
+    pub min_match_size: u32,
+    pub effort: u32,
+    pub block_size: u32,
+}
+
+impl TryFrom<RawOptions> for Options {
+    type Error = Error;
+
+    fn try_from(raw: RawOptions) -> Result<Self> {
+        let version = Version::from_raw(raw.version).ok_or(Error::InvalidVersion)?;
+        let mode = match raw.mode {
+            0 => Mode::Normal,
+            1 => Mode::RawForward,
+            2 => Mode::RawBackward,
+            _ => return Err(Error::InvalidMode),
+        };
+        // Anything but ratio is speed, as in the C wrapper
+        let quality = if raw.quality == Quality::Ratio as c_int {
+            Quality::Ratio
+        } else {
+            Quality::Speed
+        };
+
+        Ok(Self {
+            version,
+            mode,
+            quality,
+            min_match_size: raw.min_match_size,
+            effort: raw.effort,
+            block_size: raw.block_size,
+        })
+    }
+}
+
+/// Borrow `len` bytes at `data`, allowing NULL for an empty slice
+unsafe fn input_slice<'a>(data: *const u8, len: usize) -> Result<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(Error::InputNull),
+        (false, _) => Ok(unsafe { slice::from_raw_parts(data, len) }),
+    }
+}
+
+/// Borrow the output buffer described by `output` and `*output_size`
+unsafe fn output_slice<'a>(output: *mut u8, output_size: *mut usize) -> Result<&'a mut [u8]> {
+    if output_size.is_null() {
+        return Err(Error::OutputSizeNull);
+    }
+    if output.is_null() {
+        return Err(Error::OutputNull);
+    }
+
+    Ok(unsafe { slice::from_raw_parts_mut(output, *output_size) })
+}
+
+/// Store a result's size in `*output_size` and return its code
+unsafe fn finish(result: Result<usize>, output_size: *mut usize) -> c_int {
+    match result {
+        Ok(size) => {
+            if !output_size.is_null() {
+                unsafe { *output_size = size };
+            }
+            0
+        }
+        Err(error) => error_code(error),
+    }
+}
+
+/// Compress `input` into `output` ([`compress_into`])
+///
+/// `*output_size` holds the capacity of `output` on entry and the
+/// compressed size on success.
+///
+/// # Safety
+///
+/// `input` must be valid for `input_size` bytes (or NULL when it is 0),
+/// `output` for `*output_size` bytes, and `options` NULL (for
+/// [`Options::default`]) or valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lzsa_rs_compress(
+    input: *const u8,
+    input_size: usize,
+    output: *mut u8,
+    output_size: *mut usize,
+    options: *const RawOptions,
+) -> c_int {
+    let result = (|| {
+        let input = unsafe { input_slice(input, input_size)? };
+        let output = unsafe { output_slice(output, output_size)? };
+        let options = match unsafe { options.as_ref() } {
+            Some(options) => Options::try_from(*options)?,
+            None => Options::default(),
+        };
+        compress_into(input, output, &options)
+    })();
+
+    unsafe { finish(result, output_size) }
+}
+
+/// Decompress a framed stream into `output` ([`decompress_into`])
+///
+/// `*output_size` holds the capacity of `output` on entry and the
+/// decompressed size on success.
+///
+/// # Safety
+///
+/// `input` must be valid for `input_size` bytes (or NULL when it is 0) and
+/// `output` for `*output_size` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lzsa_rs_decompress(
+    input: *const u8,
+    input_size: usize,
+    output: *mut u8,
+    output_size: *mut usize,
+) -> c_int {
+    let result = (|| {
+        let input = unsafe { input_slice(input, input_size)? };
+        let output = unsafe { output_slice(output, output_size)? };
+        decompress_into(input, output)
+    })();
+
+    unsafe { finish(result, output_size) }
+}
+
+/// Worst-case compressed size for `input_size` bytes with `options`
+/// ([`max_compressed_size_for`]), or 0 if `options` is NULL or invalid
+///
+/// # Safety
+///
+/// `options` must be NULL or valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lzsa_rs_max_compressed_size(
+    input_size: usize,
+    options: *const RawOptions,
+) -> usize {
+    match unsafe { options.as_ref() }.map(|options| Options::try_from(*options)) {
+        Some(Ok(options)) => max_compressed_size_for(input_size, &options),
+        _ => 0,
+    }
+}
+
+/// Upper bound of the decompressed size of a framed stream
+/// ([`max_decompressed_size`](crate::max_decompressed_size)), stored in
+/// `*output_size`
+///
+/// # Safety
+///
+/// `input` must be valid for `input_size` bytes (or NULL when it is 0) and
+/// `output_size` NULL or valid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn lzsa_rs_max_decompressed_size(
+    input: *const u8,
+    input_size: usize,
+    output_size: *mut usize,
+) -> c_int {
+    let result = (|| {
+        if output_size.is_null() {
+            return Err(Error::OutputSizeNull);
+        }
+        let input = unsafe { input_slice(input, input_size)? };
+        crate::max_decompressed_size(input)
+    })();
+
+    unsafe { finish(result, output_size) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    const HEADER: &str = include_str!("../csrc/lzsa_rs.h");
+
+    fn raw_options() -> RawOptions {
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+        RawOptions {
+            version: options.version as c_int,
+            mode: options.mode as c_int,
+            quality: options.quality as c_int,
+            min_match_size: options.min_match_size,
+            effort: options.effort,
+            block_size: options.block_size,
+        }
+    }
+
+    #[test]
+    fn test_capi_round_trip() {
+        let original = b"Called from C, called from C, called from C.".repeat(10);
+        let options = raw_options();
+
+        unsafe {
+            let mut compressed = vec![0u8; lzsa_rs_max_compressed_size(original.len(), &options)];
+            let mut compressed_size = compressed.len();
+            let result = lzsa_rs_compress(
+                original.as_ptr(),
+                original.len(),
+                compressed.as_mut_ptr(),
+                &mut compressed_size,
+                &options,
+            );
+            assert_eq!(result, 0);
+            compressed.truncate(compressed_size);
+
+            let mut max_size = 0;
+            let result =
+                lzsa_rs_max_decompressed_size(compressed.as_ptr(), compressed.len(), &mut max_size);
+            assert_eq!(result, 0);
+            assert!(max_size >= original.len());
+
+            // A stream with no blocks has a bound of 0
+            let empty = [0x7b, 0x9e, 0x20, 0x00, 0x00, 0x00];
+            let mut empty_size = usize::MAX;
+            let result =
+                lzsa_rs_max_decompressed_size(empty.as_ptr(), empty.len(), &mut empty_size);
+            assert_eq!((result, empty_size), (0, 0));
+
+            let mut output = vec![0u8; max_size];
+            let mut output_size = output.len();
+            let result = lzsa_rs_decompress(
+                compressed.as_ptr(),
+                compressed.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+            );
+            assert_eq!(result, 0);
+            assert_eq!(&output[..output_size], original.as_slice());
+
+            // Rust-side checks reach C callers with their own codes
+            let mut output_size = output.len();
+            let result = lzsa_rs_decompress(
+                compressed.as_ptr(),
+                compressed.len() - 1,
+                output.as_mut_ptr(),
+                &mut output_size,
+            );
+            assert_eq!(result, ERR_TRUNCATED);
+        }
+    }
+
+    #[test]
+    fn test_capi_invalid_arguments() {
+        let mut output = [0u8; 64];
+        let mut output_size = output.len();
+        let mut options = raw_options();
+
+        unsafe {
+            assert_eq!(
+                lzsa_rs_compress(
+                    ptr::null(),
+                    5,
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    &options
+                ),
+                Error::InputNull.code().unwrap()
+            );
+            assert_eq!(
+                lzsa_rs_compress(
+                    b"data".as_ptr(),
+                    4,
+                    ptr::null_mut(),
+                    &mut output_size,
+                    &options
+                ),
+                Error::OutputNull.code().unwrap()
+            );
+            assert_eq!(
+                lzsa_rs_decompress(b"data".as_ptr(), 4, output.as_mut_ptr(), ptr::null_mut()),
+                Error::OutputSizeNull.code().unwrap()
+            );
+            assert_eq!(
+                lzsa_rs_compress(
+                    b"data".as_ptr(),
+                    4,
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    ptr::null()
+                ),
+                0
+            );
+            let written = output_size;
+            output_size = output.len();
+            let mut decompressed = [0u8; 4];
+            let mut decompressed_size = decompressed.len();
+            assert_eq!(
+                lzsa_rs_decompress(
+                    output.as_ptr(),
+                    written,
+                    decompressed.as_mut_ptr(),
+                    &mut decompressed_size
+                ),
+                0
+            );
+            assert_eq!(&decompressed[..decompressed_size], b"data");
+
+            options.mode = 7;
+            assert_eq!(
+                lzsa_rs_compress(
+                    b"data".as_ptr(),
+                    4,
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    &options
+                ),
+                Error::InvalidMode.code().unwrap()
+            );
+            assert_eq!(lzsa_rs_max_compressed_size(4, &options), 0);
+
+            options.mode = 0;
+            options.version = 3;
+            assert_eq!(
+                lzsa_rs_compress(
+                    b"data".as_ptr(),
+                    4,
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    &options
+                ),
+                Error::InvalidVersion.code().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_header_in_sync() {
+        // Same layout as the options the wrapper takes
+        assert_eq!(size_of::<RawOptions>(), size_of::<Options>());
+        assert_eq!(align_of::<RawOptions>(), align_of::<Options>());
+
+        for name in [
+            "lzsa_rs_compress(",
+            "lzsa_rs_decompress(",
+            "lzsa_rs_max_compressed_size(",
+            "lzsa_rs_max_decompressed_size(",
+        ] {
+            assert!(HEADER.contains(name), "{name} missing from lzsa_rs.h");
+        }
+
+        for (name, code) in [
+            ("LZSA_RS_ERR_CHECKSUM_MISMATCH", ERR_CHECKSUM_MISMATCH),
+            ("LZSA_RS_ERR_OUT_OF_RANGE", ERR_OUT_OF_RANGE),
+            ("LZSA_RS_ERR_OUTPUT_TOO_LARGE", ERR_OUTPUT_TOO_LARGE),
+            ("LZSA_RS_ERR_INVALID_UTF8", ERR_INVALID_UTF8),
+            ("LZSA_RS_ERR_TRUNCATED", ERR_TRUNCATED),
+            ("LZSA_RS_ERR_IO", ERR_IO),
+        ] {
+            let line = format!("{name} = {code},");
+            assert!(HEADER.contains(&line), "`{line}` missing from lzsa_rs.h");
+        }
+    }
+}
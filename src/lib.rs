@@ -7,19 +7,80 @@
 //! ## Example
 //!
 //! ```rust
+//! # #[cfg(feature = "v1")] {
 //! use lzsa_sys::{compress_v1, decompress};
 //!
 //! let original = b"Hello, world! This is test data.";
 //! let compressed = compress_v1(original)?;
 //! let decompressed = decompress(&compressed)?;
 //! assert_eq!(original, decompressed.as_slice());
+//! # }
 //! # Ok::<(), lzsa_sys::Error>(())
 //! ```
+//!
+//! ## `no_std`
+//!
+//! With `default-features = false` the crate is `no_std`. The slice-based
+//! [`compress_into`] and [`decompress_into`] are always available; functions
+//! returning `Vec` need the `alloc` feature, and the [`stream`] module needs
+//! `std`.
+//!
+//! ## Block formats
+//!
+//! The `v1` and `v2` features (both on by default) select which formats are
+//! built. Disabling one removes its C code and its version-specific functions
+//! such as `compress_v1`; the generic functions then return
+//! `Error::InvalidVersion` for it.
+//!
+//! ## Thread safety
+//!
+//! The C code keeps no global state: each call sets up its own compressor or
+//! decompressor context and frees it before returning, so all functions may
+//! run on any number of threads at once. Every type in the crate is `Send`
+//! and `Sync` (the [`stream`] adapters when the wrapped reader or writer is).
+//! [`Compressor`] and [`Decompressor`] only hold Rust buffers, and a
+//! [`Context`] owns a C compressor no other value touches, but their
+//! methods take `&mut self`; share one behind a `Mutex`, keep one per
+//! thread or task, or lend contexts out from a [`ContextPool`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// The generated bindings differ nominally from the hand-written ones (C enums
+// become `c_uint`, `Options` is `lzsa_options_t`); the lint is only honored
+// at crate level
+#![cfg_attr(feature = "bindgen", allow(clashing_extern_declarations))]
+
+#[cfg(not(any(feature = "v1", feature = "v2")))]
+compile_error!("at least one of the `v1` and `v2` features must be enabled");
 
-use std::os::raw::c_int;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::ffi::c_int;
+
+#[cfg(feature = "alloc-hook")]
+pub mod alloc_hook;
+#[cfg(feature = "bench-util")]
+pub mod bench;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "alloc")]
+mod crc32;
+pub mod prelude;
+#[cfg(feature = "alloc")]
+pub mod seekable;
+#[cfg(feature = "std")]
+pub mod stream;
 
 /// LZSA compression format version
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// These are the formats the upstream `lzsa` tool selects with `-f 1` (the
+/// default) and `-f 2`; its framed output is what [`decompress`] reads.
+/// Versions order by their number, so `Version::V2 > Version::V1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 #[repr(i32)]
 pub enum Version {
     /// LZSA1 format - faster decompression
@@ -28,18 +89,55 @@ pub enum Version {
     V2 = 2,
 }
 
+impl Version {
+    /// Map a version number reported by the C wrapper
+    pub(crate) fn from_raw(version: c_int) -> Option<Self> {
+        match version {
+            1 => Some(Self::V1),
+            2 => Some(Self::V2),
+            _ => None,
+        }
+    }
+
+    /// The stream header of framed data in this version: [`FRAME_MAGIC`]
+    /// followed by the version byte
+    pub fn frame_magic(self) -> &'static [u8] {
+        match self {
+            Self::V1 => &[FRAME_MAGIC[0], FRAME_MAGIC[1], 0x00],
+            Self::V2 => &[FRAME_MAGIC[0], FRAME_MAGIC[1], 0x20],
+        }
+    }
+
+    /// Valid `Options::min_match_size` values for this version
+    pub(crate) fn min_match_sizes(self) -> core::ops::RangeInclusive<u32> {
+        match self {
+            Self::V1 => MIN_MATCH_V1..=MAX_MIN_MATCH,
+            Self::V2 => MIN_MATCH_V2..=MAX_MIN_MATCH,
+        }
+    }
+}
+
 /// Compression mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 #[repr(i32)]
 pub enum Mode {
     /// Forward compression with framing (default)
     Normal = 0,
     /// Forward compression, raw block (no frame header)
     RawForward = 1,
+    /// Backward compression, raw block (no frame header), for decompressors
+    /// that unpack from the end of the data towards the start (`lzsa -r -b`)
+    ///
+    /// Decompress with [`decompress_v1_backward`] or [`decompress_v2_backward`].
+    RawBackward = 2,
 }
 
 /// Compression quality setting
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 #[repr(i32)]
 pub enum Quality {
     /// Favor decompression speed
@@ -48,27 +146,325 @@ pub enum Quality {
     Ratio = 1,
 }
 
+impl Quality {
+    /// The effort level this setting stands for: 0 for `Speed`, 9 for `Ratio`
+    pub fn effort(self) -> u32 {
+        match self {
+            Self::Speed => 0,
+            Self::Ratio => 9,
+        }
+    }
+}
+
 /// Compression options
-#[derive(Debug, Clone, Copy)]
+///
+/// `min_match_size` must be 3 to 5 for LZSA1 and 2 to 5 for LZSA2, otherwise
+/// compression fails with `Error::InvalidMinMatch`. Larger values compress
+/// faster and decompress slightly faster, at the cost of ratio. The value is
+/// passed to the compressor unchanged whatever the `quality` or `effort`:
+/// no setting overrides another, so there are no combinations that are
+/// accepted but ignored. `Quality::Speed` output still differs from
+/// `Quality::Ratio` output at the same minimum, because the optimizer then
+/// picks the matches that decode fastest rather than the shortest encoding.
+///
+/// `effort` selects a level from 0 to 9 in place of `quality`; the default,
+/// [`Options::EFFORT_FROM_QUALITY`], uses [`Quality::effort`]. Levels above
+/// 9 are treated as 9. Upstream's optimizer currently has a single switch,
+/// so levels 0 to 4 compress like `Quality::Speed` and 5 to 9 like
+/// `Quality::Ratio`, with the boundary kept stable if finer control is
+/// exposed later. Both halves take about the same time to compress; they
+/// trade ratio against decompression speed.
+///
+/// `block_size` is the number of input bytes per block frame in
+/// `Mode::Normal`, from 1 to [`BLOCK_SIZE`] (the default); other values fail
+/// with `Error::InvalidBlockSize`. Decompressors on small machines may need
+/// smaller blocks, at some cost in ratio and 3 bytes of framing per block.
+/// Raw modes ignore it.
+///
+/// Every `mode` works with every `version` built into the crate:
+///
+/// | `mode`        | LZSA1 | LZSA2 | Input                  | Read back with                                         |
+/// |---------------|-------|-------|------------------------|--------------------------------------------------------|
+/// | `Normal`      | yes   | yes   | any size               | [`decompress`]                                         |
+/// | `RawForward`  | yes   | yes   | at most [`BLOCK_SIZE`] | [`decompress_raw`]                                     |
+/// | `RawBackward` | yes   | yes   | at most [`BLOCK_SIZE`] | [`decompress_v1_backward`], [`decompress_v2_backward`] |
+///
+/// Backward output only exists as a raw block, as in upstream's `lzsa -r -b`;
+/// there is no framed backward stream. Raw input larger than a block fails
+/// with `Error::InvalidBlockSize`, and a version left out of the build with
+/// `Error::InvalidVersion`.
+///
+/// `Options` is `#[non_exhaustive]`, so that fields can be added without a
+/// breaking release. Outside this crate, start from `Options::default()`
+/// and set fields on it, or use [`Options::builder`]; struct literals,
+/// including those ending in `..Options::default()`, do not compile:
+///
+/// ```compile_fail
+/// let options = lzsa_sys::Options {
+///     version: lzsa_sys::Version::V2,
+///     ..lzsa_sys::Options::default()
+/// };
+/// ```
+///
+/// ```rust
+/// use lzsa_sys::{Options, Version};
+///
+/// let mut options = Options::default();
+/// options.version = Version::V2;
+/// assert_eq!(options, Options::builder().version(Version::V2).build());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
+#[non_exhaustive]
 pub struct Options {
     pub version: Version,
     pub mode: Mode,
     pub quality: Quality,
     pub min_match_size: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default = "effort_from_quality",
+            skip_serializing_if = "is_effort_from_quality"
+        )
+    )]
+    pub effort: u32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default = "default_block_size",
+            skip_serializing_if = "is_default_block_size"
+        )
+    )]
+    pub block_size: u32,
+}
+
+impl Options {
+    /// `effort` value that defers to `quality`
+    pub const EFFORT_FROM_QUALITY: u32 = u32::MAX;
+
+    /// Start building options from the defaults
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::new()
+    }
+
+    /// Return a copy with out-of-range values moved to the nearest valid one
+    ///
+    /// `min_match_size` is clamped to the range of `version` (3 to 5 for
+    /// LZSA1, 2 to 5 for LZSA2), `effort` above 9 becomes 9 unless it is
+    /// [`EFFORT_FROM_QUALITY`](Self::EFFORT_FROM_QUALITY), and `block_size`
+    /// is clamped to 1 to [`BLOCK_SIZE`]. Compressing never does this on its
+    /// own: out-of-range values are still errors unless the options are
+    /// clamped first. A version left out of the build stays unsupported.
+    pub fn clamped(self) -> Options {
+        let sizes = self.version.min_match_sizes();
+        let effort = if self.effort == Self::EFFORT_FROM_QUALITY {
+            self.effort
+        } else {
+            self.effort.min(9)
+        };
+
+        Options {
+            min_match_size: self.min_match_size.clamp(*sizes.start(), *sizes.end()),
+            effort,
+            block_size: self.block_size.clamp(1, BLOCK_SIZE as u32),
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn effort_from_quality() -> u32 {
+    Options::EFFORT_FROM_QUALITY
+}
+
+#[cfg(feature = "serde")]
+fn is_effort_from_quality(effort: &u32) -> bool {
+    *effort == Options::EFFORT_FROM_QUALITY
+}
+
+#[cfg(feature = "serde")]
+fn default_block_size() -> u32 {
+    BLOCK_SIZE as u32
+}
+
+#[cfg(feature = "serde")]
+fn is_default_block_size(block_size: &u32) -> bool {
+    *block_size == BLOCK_SIZE as u32
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
-            version: Version::V1,
+            // LZSA1 unless it is left out of the build
+            version: if cfg!(feature = "v1") {
+                Version::V1
+            } else {
+                Version::V2
+            },
             mode: Mode::Normal,
             quality: Quality::Ratio,
-            min_match_size: 3,
+            min_match_size: MIN_MATCH_V1,
+            effort: Self::EFFORT_FROM_QUALITY,
+            block_size: BLOCK_SIZE as u32,
+        }
+    }
+}
+
+/// `LZSA1` or `LZSA2`
+impl core::fmt::Display for Version {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::V1 => f.write_str("LZSA1"),
+            Self::V2 => f.write_str("LZSA2"),
+        }
+    }
+}
+
+/// The names used in config files: `normal`, `raw-forward`, `raw-backward`
+impl core::fmt::Display for Mode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Normal => f.write_str("normal"),
+            Self::RawForward => f.write_str("raw-forward"),
+            Self::RawBackward => f.write_str("raw-backward"),
+        }
+    }
+}
+
+/// The names used in config files: `speed`, `ratio`
+impl core::fmt::Display for Quality {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Speed => f.write_str("speed"),
+            Self::Ratio => f.write_str("ratio"),
+        }
+    }
+}
+
+/// A one-line summary for logs, such as `LZSA2 normal ratio min_match=3`;
+/// `effort` and `block_size` are only shown when not the default
+impl core::fmt::Display for Options {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} {} {} min_match={}",
+            self.version, self.mode, self.quality, self.min_match_size
+        )?;
+        if self.effort != Self::EFFORT_FROM_QUALITY {
+            write!(f, " effort={}", self.effort)?;
         }
+        if self.block_size as usize != BLOCK_SIZE {
+            write!(f, " block_size={}", self.block_size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the default options as defined by the C wrapper
+///
+/// These match `Options::default()`; a test keeps the two in sync.
+pub fn c_default_options() -> Options {
+    unsafe { lzsa_get_default_options() }
+}
+
+/// Builder for [`Options`], starting from `Options::default()`
+///
+/// ```rust
+/// use lzsa_sys::{OptionsBuilder, Quality, Version};
+///
+/// let options = OptionsBuilder::new()
+///     .version(Version::V2)
+///     .quality(Quality::Speed)
+///     .build();
+/// assert_eq!(options.version, Version::V2);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl OptionsBuilder {
+    /// Start from the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the format version
+    pub fn version(mut self, version: Version) -> Self {
+        self.options.version = version;
+        self
+    }
+
+    /// Set the compression mode
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.options.mode = mode;
+        self
+    }
+
+    /// Set the quality/speed tradeoff
+    pub fn quality(mut self, quality: Quality) -> Self {
+        self.options.quality = quality;
+        self
+    }
+
+    /// Set the minimum match size (not validated until compression)
+    pub fn min_match_size(mut self, min_match_size: u32) -> Self {
+        self.options.min_match_size = min_match_size;
+        self
+    }
+
+    /// Set the effort level (0 to 9), overriding the quality setting
+    pub fn effort(mut self, effort: u32) -> Self {
+        self.options.effort = effort;
+        self
+    }
+
+    /// Set the input bytes per block frame (not validated until compression)
+    pub fn block_size(mut self, block_size: u32) -> Self {
+        self.options.block_size = block_size;
+        self
+    }
+
+    /// Get the configured options
+    pub fn build(self) -> Options {
+        self.options
     }
 }
 
+/// Statistics reported by [`compress_with_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Uncompressed size in bytes
+    pub input_len: usize,
+    /// Compressed size in bytes, including framing
+    pub output_len: usize,
+    /// Number of block frames (up to 64 KiB of input each)
+    pub block_count: usize,
+    /// Commands (a literal run and a match) in blocks that were compressed;
+    /// blocks stored as-is add none
+    pub command_count: usize,
+    /// Matches in blocks that were compressed, from the compressor's own
+    /// statistics (as printed by `lzsa -stats`)
+    pub match_count: usize,
+    /// Literal bytes in blocks that were compressed; the bytes of stored
+    /// blocks are not counted
+    pub literal_count: usize,
+}
+
+/// Statistics as filled in by the C wrapper (`lzsa_stats_t`)
+#[derive(Debug, Default)]
+#[repr(C)]
+struct RawStats {
+    input_size: usize,
+    output_size: usize,
+    block_count: usize,
+    command_count: usize,
+    match_count: usize,
+    literal_count: usize,
+}
+
 /// Error type for LZSA operations
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
@@ -80,13 +476,65 @@ pub enum Error {
     DecompressionFailed,
     InvalidFormat,
     InvalidVersion,
+    /// The C side or a Rust buffer allocation failed to get memory; the
+    /// buffers the crate sizes from the input are allocated fallibly, so
+    /// huge inputs return this rather than aborting
     OutOfMemory,
     InvalidMode,
+    InvalidBlockSize,
+    InvalidMinMatch,
+    ChecksumMismatch,
+    /// A requested range reaches past the end of the data
+    OutOfRange,
+    /// Decompressed data would exceed the caller's output limit
+    OutputTooLarge,
+    /// Decompressed data is not valid UTF-8
+    InvalidUtf8,
+    /// A framed stream ends before the block or footer its frames announce,
+    /// e.g. a partially downloaded file
+    Truncated,
+    /// Reading or writing failed (only the error kind is kept, so `Error`
+    /// stays `Copy`)
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
     Unknown(i32),
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Error {
+    /// The C wrapper's numeric error code for this error
+    ///
+    /// This is stable across releases and suited for logging. `Unknown`
+    /// returns the unrecognized code it carries. `None` is reserved for
+    /// errors that do not originate from the C side.
+    pub fn code(&self) -> Option<i32> {
+        let code = match self {
+            Self::InputNull => -1,
+            Self::OutputNull => -2,
+            Self::OutputSizeNull => -3,
+            Self::BufferTooSmall => -4,
+            Self::CompressionFailed => -5,
+            Self::DecompressionFailed => -6,
+            Self::InvalidFormat => -7,
+            Self::InvalidVersion => -8,
+            Self::OutOfMemory => -9,
+            Self::InvalidMode => -10,
+            Self::InvalidBlockSize => -11,
+            Self::InvalidMinMatch => -12,
+            Self::Unknown(code) => *code,
+            Self::ChecksumMismatch
+            | Self::OutOfRange
+            | Self::OutputTooLarge
+            | Self::InvalidUtf8
+            | Self::Truncated => return None,
+            #[cfg(feature = "std")]
+            Self::Io(_) => return None,
+        };
+        Some(code)
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::InputNull => write!(f, "Input pointer is NULL"),
             Self::OutputNull => write!(f, "Output pointer is NULL"),
@@ -98,12 +546,40 @@ impl std::fmt::Display for Error {
             Self::InvalidVersion => write!(f, "Invalid version specified"),
             Self::OutOfMemory => write!(f, "Out of memory"),
             Self::InvalidMode => write!(f, "Invalid compression mode"),
+            Self::InvalidBlockSize => write!(f, "Invalid block size"),
+            Self::InvalidMinMatch => write!(f, "Minimum match size out of range for version"),
+            Self::ChecksumMismatch => write!(f, "Checksum mismatch"),
+            Self::OutOfRange => write!(f, "Range out of bounds"),
+            Self::OutputTooLarge => write!(f, "Decompressed data exceeds the output limit"),
+            Self::InvalidUtf8 => write!(f, "Decompressed data is not valid UTF-8"),
+            Self::Truncated => write!(f, "Compressed data is truncated"),
+            #[cfg(feature = "std")]
+            Self::Io(kind) => write!(f, "I/O error: {}", kind),
             Self::Unknown(code) => write!(f, "Unknown error: {}", code),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(kind) => kind.into(),
+            Error::Truncated => std::io::Error::new(std::io::ErrorKind::UnexpectedEof, error),
+            error => std::io::Error::other(error),
+        }
+    }
+}
+
+/// Only the error kind is kept; the message and any source are dropped
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error.kind())
+    }
+}
 
 impl From<i32> for Error {
     fn from(code: i32) -> Self {
@@ -118,16 +594,24 @@ impl From<i32> for Error {
             -8 => Self::InvalidVersion,
             -9 => Self::OutOfMemory,
             -10 => Self::InvalidMode,
+            -11 => Self::InvalidBlockSize,
+            -12 => Self::InvalidMinMatch,
             _ => Self::Unknown(code),
         }
     }
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
-// FFI bindings
+/// Opaque compression context owned by the C wrapper (`lzsa_context_t`)
+#[repr(C)]
+struct RawContext {
+    _private: [u8; 0],
+}
+
+// FFI bindings (some are only used by alloc/std-gated wrappers)
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 unsafe extern "C" {
-    #[allow(dead_code)]
     fn lzsa_get_default_options() -> Options;
 
     fn lzsa_get_max_compressed_size(input_size: usize, version: c_int, mode: c_int) -> usize;
@@ -142,6 +626,7 @@ unsafe extern "C" {
         options: *const Options,
     ) -> c_int;
 
+    #[cfg(feature = "v1")]
     fn lzsa_compress_v1(
         input: *const u8,
         input_size: usize,
@@ -149,6 +634,7 @@ unsafe extern "C" {
         output_size: *mut usize,
     ) -> c_int;
 
+    #[cfg(feature = "v2")]
     fn lzsa_compress_v2(
         input: *const u8,
         input_size: usize,
@@ -164,6 +650,7 @@ unsafe extern "C" {
         detected_version: *mut c_int,
     ) -> c_int;
 
+    #[cfg(feature = "v1")]
     fn lzsa_decompress_v1(
         input: *const u8,
         input_size: usize,
@@ -171,241 +658,5527 @@ unsafe extern "C" {
         output_size: *mut usize,
     ) -> c_int;
 
+    #[cfg(feature = "v2")]
     fn lzsa_decompress_v2(
         input: *const u8,
         input_size: usize,
         output: *mut u8,
         output_size: *mut usize,
     ) -> c_int;
-}
 
-// High-level Rust API
-
-/// Compress data with custom options
-pub fn compress_with_options(input: &[u8], options: &Options) -> Result<Vec<u8>> {
-    if input.is_empty() {
-        return Ok(Vec::new());
-    }
+    fn lzsa_decompress_raw(
+        input: *const u8,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        version: c_int,
+    ) -> c_int;
 
-    unsafe {
-        let max_size = lzsa_get_max_compressed_size(
-            input.len(),
-            options.version as c_int,
-            options.mode as c_int,
-        );
+    fn lzsa_compress_with_stats(
+        input: *const u8,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        options: *const Options,
+        stats: *mut RawStats,
+    ) -> c_int;
 
-        let mut output = vec![0u8; max_size];
-        let mut output_size = max_size;
+    fn lzsa_compress_with_dictionary(
+        input: *const u8,
+        input_size: usize,
+        dictionary: *const u8,
+        dictionary_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        options: *const Options,
+    ) -> c_int;
 
-        let result = lzsa_compress(
-            input.as_ptr(),
-            input.len(),
-            output.as_mut_ptr(),
-            &mut output_size,
-            options as *const Options,
-        );
+    fn lzsa_decompress_with_dictionary(
+        input: *const u8,
+        input_size: usize,
+        dictionary: *const u8,
+        dictionary_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        detected_version: *mut c_int,
+    ) -> c_int;
 
-        if result != 0 {
-            return Err(Error::from(result));
-        }
+    fn lzsa_encode_stream_header(output: *mut u8, output_size: *mut usize, version: c_int)
+    -> c_int;
 
-        output.truncate(output_size);
-        Ok(output)
-    }
-}
+    fn lzsa_encode_stream_footer(output: *mut u8, output_size: *mut usize) -> c_int;
 
-/// Compress with LZSA1 (convenience function)
-pub fn compress_v1(input: &[u8]) -> Result<Vec<u8>> {
-    if input.is_empty() {
-        return Ok(Vec::new());
-    }
+    fn lzsa_compress_block(
+        window: *const u8,
+        previous_size: usize,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        options: *const Options,
+    ) -> c_int;
 
-    unsafe {
-        let max_size = lzsa_get_max_compressed_size(input.len(), 1, 0);
-        let mut output = vec![0u8; max_size];
-        let mut output_size = max_size;
+    #[cfg(all(feature = "alloc", feature = "v2"))]
+    fn lzsa_compress_block_data(
+        window: *const u8,
+        previous_size: usize,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        options: *const Options,
+    ) -> c_int;
 
-        let result = lzsa_compress_v1(
-            input.as_ptr(),
-            input.len(),
-            output.as_mut_ptr(),
-            &mut output_size,
-        );
+    fn lzsa_decode_stream_header(input: *const u8, input_size: usize, version: *mut c_int)
+    -> c_int;
 
-        if result != 0 {
-            return Err(Error::from(result));
-        }
+    fn lzsa_decode_block_frame(
+        input: *const u8,
+        input_size: usize,
+        block_size: *mut usize,
+        is_uncompressed: *mut c_int,
+    ) -> c_int;
 
-        output.truncate(output_size);
-        Ok(output)
-    }
-}
+    fn lzsa_decompress_block(
+        input: *const u8,
+        input_size: usize,
+        window: *mut u8,
+        previous_size: usize,
+        output_size: *mut usize,
+        version: c_int,
+    ) -> c_int;
 
-/// Compress with LZSA2 (convenience function)
-pub fn compress_v2(input: &[u8]) -> Result<Vec<u8>> {
-    if input.is_empty() {
-        return Ok(Vec::new());
-    }
+    fn lzsa_context_create(options: *const Options, context: *mut *mut RawContext) -> c_int;
 
-    unsafe {
-        let max_size = lzsa_get_max_compressed_size(input.len(), 2, 0);
-        let mut output = vec![0u8; max_size];
-        let mut output_size = max_size;
+    #[cfg(all(test, feature = "std", feature = "v1", feature = "v2"))]
+    fn lzsa_options_layout(offsets: *mut usize, count: usize, align: *mut usize) -> usize;
 
-        let result = lzsa_compress_v2(
-            input.as_ptr(),
-            input.len(),
-            output.as_mut_ptr(),
-            &mut output_size,
-        );
+    #[cfg(all(test, feature = "std", feature = "v1", feature = "v2"))]
+    fn lzsa_stats_layout(offsets: *mut usize, count: usize, align: *mut usize) -> usize;
 
-        if result != 0 {
-            return Err(Error::from(result));
-        }
+    fn lzsa_context_destroy(context: *mut RawContext);
 
-        output.truncate(output_size);
-        Ok(output)
-    }
-}
+    fn lzsa_context_compress(
+        context: *mut RawContext,
+        input: *const u8,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+    ) -> c_int;
 
-/// Decompress data (auto-detects LZSA1 or LZSA2)
-pub fn decompress(input: &[u8]) -> Result<Vec<u8>> {
-    if input.is_empty() {
-        return Ok(Vec::new());
-    }
+    fn lzsa_context_decompress(
+        context: *mut RawContext,
+        input: *const u8,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+        detected_version: *mut c_int,
+    ) -> c_int;
 
-    unsafe {
-        let max_size = lzsa_get_max_decompressed_size(input.as_ptr(), input.len());
+    #[cfg(feature = "v1")]
+    fn lzsa_decompress_v1_backward(
+        input: *const u8,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+    ) -> c_int;
 
-        if max_size == 0 {
-            return Err(Error::DecompressionFailed);
-        }
+    #[cfg(feature = "v2")]
+    fn lzsa_decompress_v2_backward(
+        input: *const u8,
+        input_size: usize,
+        output: *mut u8,
+        output_size: *mut usize,
+    ) -> c_int;
+}
 
-        let mut output = vec![0u8; max_size];
-        let mut output_size = max_size;
-        let mut detected_version: c_int = 0;
+// Bindings generated from csrc/lzsa_wrapper.h, only used to check the
+// hand-written declarations above
+#[cfg(feature = "bindgen")]
+#[allow(dead_code, non_camel_case_types, non_upper_case_globals, clippy::all)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
 
-        let result = lzsa_decompress(
-            input.as_ptr(),
-            input.len(),
-            output.as_mut_ptr(),
-            &mut output_size,
-            &mut detected_version,
+/// Run `$body`, a C compress or decompress call checked into a
+/// `Result<usize>` of the output size, inside a `tracing` span `$name`
+///
+/// The span records `version` (a `Version`, or an `Option<Version>` for
+/// streams whose header the C code reads), `mode` and `input_len`; then
+/// `output_len` or `error` once the call returns. `$body` runs in a closure
+/// either way, so `?` and `return` in it leave the call, not the caller.
+/// Without the `tracing` feature the other arguments are not evaluated.
+#[cfg(feature = "tracing")]
+macro_rules! traced {
+    ($name:literal, $version:expr, $mode:expr, $input_len:expr, $body:expr) => {{
+        let span = tracing::debug_span!(
+            $name,
+            version = tracing::field::Empty,
+            mode = %$mode,
+            input_len = $input_len,
+            output_len = tracing::field::Empty,
+            error = tracing::field::Empty,
         );
-
-        if result != 0 {
-            return Err(Error::from(result));
+        if let Some(version) = Option::<Version>::from($version) {
+            span.record("version", tracing::field::display(version));
         }
+        let result = span.in_scope(|| -> Result<usize> { $body });
+        match &result {
+            Ok(len) => span.record("output_len", len),
+            Err(error) => span.record("error", tracing::field::display(error)),
+        };
+        result
+    }};
+}
 
-        output.truncate(output_size);
-        Ok(output)
-    }
+#[cfg(not(feature = "tracing"))]
+macro_rules! traced {
+    ($name:literal, $version:expr, $mode:expr, $input_len:expr, $body:expr) => {
+        traced_call(|| $body)
+    };
 }
 
-/// Decompress LZSA1 data
-pub fn decompress_v1(input: &[u8]) -> Result<Vec<u8>> {
-    if input.is_empty() {
+/// Run the body of a `traced!` call when the span is compiled out
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+fn traced_call(body: impl FnOnce() -> Result<usize>) -> Result<usize> {
+    body()
+}
+
+/// Largest block a framed LZSA stream can carry (64 KiB)
+pub const BLOCK_SIZE: usize = 65536;
+
+/// Largest accepted [`Options::block_size`], the same as [`BLOCK_SIZE`]
+pub const MAX_BLOCK_SIZE: usize = BLOCK_SIZE;
+
+/// Smallest accepted [`Options::min_match_size`] for LZSA1
+/// (`MIN_MATCH_SIZE_V1` in upstream `format.h`)
+pub const MIN_MATCH_V1: u32 = 3;
+
+/// Smallest accepted [`Options::min_match_size`] for LZSA2
+/// (`MIN_MATCH_SIZE_V2` in upstream `format.h`)
+pub const MIN_MATCH_V2: u32 = 2;
+
+/// Largest accepted [`Options::min_match_size`] for either version
+pub const MAX_MIN_MATCH: u32 = 5;
+
+/// Magic bytes starting every framed stream, followed by the version byte
+/// (`LZSA_ID_0` and `LZSA_ID_1` upstream)
+pub const FRAME_MAGIC: [u8; 2] = [0x7b, 0x9e];
+
+/// Size of the smallest framed stream that holds any data: a stream header,
+/// one block frame with a single byte of data, and the footer
+///
+/// Inputs shorter than this always compress to more bytes than they have.
+pub const MIN_STREAM_SIZE: usize = HEADER_SIZE + 2 * FRAME_SIZE + 1;
+
+/// Size of the stream header that starts a framed stream
+pub(crate) const HEADER_SIZE: usize = 3;
+
+/// Size of a block frame header in a framed stream
+pub(crate) const FRAME_SIZE: usize = 3;
+
+/// Header fields of a framed LZSA stream, see [`parse_frame_header`]
+///
+/// These are all the fields the format has, for LZSA1 and LZSA2 alike. The
+/// third header byte only encodes the version (`0x00` for LZSA1, `0x20` for
+/// LZSA2) and carries no option flags, and the one flag in a block frame is
+/// the stored-block bit surfaced as `uncompressed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Format version from the stream header
+    pub version: Version,
+    /// Whether the first block is stored as-is rather than compressed
+    pub uncompressed: bool,
+    /// Size of the first block's data as declared by its frame, or `None`
+    /// if no block follows the stream header
+    pub block_size: Option<usize>,
+    /// Decompressed size of the stream, when its frames record it
+    ///
+    /// LZSA headers have no size field. The size is only known without
+    /// decompressing when the stream is a single stored block (whose frame
+    /// gives its size) followed by the footer, or has no blocks at all;
+    /// otherwise this is `None` and [`max_decompressed_size`] gives a
+    /// bound instead. It is derived from the block frame rather than read
+    /// from a size field, so [`decompress`] has nothing to check it against.
+    pub original_size: Option<usize>,
+}
+
+impl FrameHeader {
+    /// Format version from the stream header
+    pub fn version(&self) -> Version {
+        self.version
+    }
+}
+
+// High-level Rust API
+
+/// Get the maximum compressed size for an input of `input_len` bytes
+///
+/// Use this to size the buffer passed to [`compress_into`] with the default
+/// block size ([`max_compressed_size_for`] covers others). Incompressible
+/// data is stored as-is, so the worst case is the input itself plus framing:
+/// a 3-byte stream header, a 3-byte frame per 64 KiB block and a 3-byte
+/// footer, i.e. `input_len + 6 + 3 * ceil(input_len / 65536)`.
+pub fn max_compressed_size(input_len: usize, version: Version, mode: Mode) -> usize {
+    unsafe { lzsa_get_max_compressed_size(input_len, version as c_int, mode as c_int) }
+}
+
+/// [`max_compressed_size`] as a `const fn`, for sizing buffers at compile
+/// time
+///
+/// The C bound cannot be called in constant context, so this is the same
+/// formula in Rust: `input_len + 6 + 3 * ceil(input_len / 65536)`, for
+/// every version and mode. A test keeps the two in agreement.
+///
+/// ```rust
+/// use lzsa_sys::{compress_fixed, max_compressed_size_const, Options, Version};
+///
+/// const N: usize = max_compressed_size_const(256, Version::V1);
+/// let message = [b'x'; 256];
+/// let (buffer, len) = compress_fixed::<N>(&message, &Options::default())?;
+/// assert!(len <= buffer.len());
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+pub const fn max_compressed_size_const(input_len: usize, version: Version) -> usize {
+    // Blocks have the same worst case, stored as-is, in both versions
+    let _ = version;
+    HEADER_SIZE + input_len.div_ceil(BLOCK_SIZE) * FRAME_SIZE + input_len + FRAME_SIZE
+}
+
+/// Get the maximum compressed size for an input of `input_len` bytes
+/// compressed with `options`
+///
+/// This is [`max_compressed_size`] plus 3 bytes for every block frame that
+/// an `options.block_size` smaller than 64 KiB adds.
+pub fn max_compressed_size_for(input_len: usize, options: &Options) -> usize {
+    let max_size = max_compressed_size(input_len, options.version, options.mode);
+    let block_size = options.block_size as usize;
+    if options.mode != Mode::Normal || block_size == 0 || block_size >= BLOCK_SIZE {
+        return max_size;
+    }
+
+    let extra_blocks = input_len.div_ceil(block_size) - input_len.div_ceil(BLOCK_SIZE);
+    max_size + extra_blocks * FRAME_SIZE
+}
+
+/// Compress data with custom options
+///
+/// In `Mode::Normal` inputs of any size are split into as many 64 KiB block
+/// frames as needed. Raw modes produce a single block of at most
+/// [`BLOCK_SIZE`] bytes, and larger inputs return `Error::InvalidBlockSize`;
+/// use `Mode::Normal`, or split the input and compress each part (as
+/// `compress_parallel` does), instead.
+///
+/// A framed stream is at least [`MIN_STREAM_SIZE`] bytes, so inputs shorter
+/// than that always expand; [`compress_smart`] stores those instead.
+#[cfg(feature = "alloc")]
+pub fn compress_with_options(input: &[u8], options: &Options) -> Result<Vec<u8>> {
+    if input.is_empty() {
         return Ok(Vec::new());
     }
 
-    unsafe {
-        let max_size = lzsa_get_max_decompressed_size(input.as_ptr(), input.len());
+    let max_size = max_compressed_size_for(input.len(), options);
 
-        if max_size == 0 {
-            return Err(Error::DecompressionFailed);
+    let mut output = try_zeroed(max_size)?;
+    let output_size = compress_into(input, &mut output, options)?;
+    output.truncate(output_size);
+    Ok(output)
+}
+
+/// Compress data into a caller-provided buffer
+///
+/// Returns the number of bytes written to `output`, or
+/// `Error::BufferTooSmall` if the compressed data does not fit. Nothing is
+/// allocated on the Rust side, so one scratch buffer can be reused across
+/// calls; the C compressor still allocates its own working state.
+pub fn compress_into(input: &[u8], output: &mut [u8], options: &Options) -> Result<usize> {
+    if input.is_empty() {
+        return Ok(0);
+    }
+
+    traced!(
+        "compress",
+        options.version,
+        options.mode,
+        input.len(),
+        unsafe {
+            let mut output_size = output.len();
+
+            let result = lzsa_compress(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+                options as *const Options,
+            );
+
+            if result < 0 {
+                let error = Error::from(result);
+
+                // The compressor reports running out of room as a plain failure,
+                // which cannot happen when the buffer has the worst-case size
+                let max_size = max_compressed_size_for(input.len(), options);
+                if error == Error::CompressionFailed && output.len() < max_size {
+                    return Err(Error::BufferTooSmall);
+                }
+
+                return Err(error);
+            }
+
+            checked_compress(result, output_size, output.len())
+        }
+    )
+}
+
+/// Compress data into `output`, reusing its allocation
+///
+/// `output` is cleared, grown to the worst-case compressed size if it is
+/// smaller, and truncated to the compressed data, so a `Vec` kept across
+/// calls stops allocating once it has held the largest output. The data is
+/// the same as [`compress_with_options`] returns. On error `output` is left
+/// empty.
+#[cfg(feature = "alloc")]
+pub fn compress_into_vec(input: &[u8], output: &mut Vec<u8>, options: &Options) -> Result<()> {
+    output.clear();
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    try_resize(output, max_compressed_size_for(input.len(), options))?;
+    let result = compress_into(input, output, options);
+    let output_size = result.inspect_err(|_| output.clear())?;
+    output.truncate(output_size);
+    Ok(())
+}
+
+/// Compress `input` into a stack array of `N` bytes, returning the array
+/// and the number of bytes used
+///
+/// Nothing is allocated on the Rust side, which suits small, latency
+/// critical messages; the C compressor still allocates its own working
+/// state. Returns `Error::BufferTooSmall` if the output does not fit in `N`
+/// bytes. `N = max_compressed_size(len, ..)` always suffices for inputs of
+/// up to `len` bytes; `len + 9` for any `len` up to 64 KiB:
+///
+/// ```rust
+/// use lzsa_sys::{compress_fixed, decompress, max_compressed_size, Mode, Options, Version};
+///
+/// // Messages are at most 256 bytes
+/// const N: usize = 256 + 9;
+/// assert_eq!(max_compressed_size(256, Version::V1, Mode::Normal), N);
+///
+/// let (buffer, len) = compress_fixed::<N>(b"sample, sample, sample", &Options::default())?;
+/// assert_eq!(decompress(&buffer[..len])?, b"sample, sample, sample");
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+pub fn compress_fixed<const N: usize>(input: &[u8], options: &Options) -> Result<([u8; N], usize)> {
+    let mut output = [0u8; N];
+    let output_size = compress_into(input, &mut output, options)?;
+    Ok((output, output_size))
+}
+
+/// Reusable compressor that keeps its output buffer between calls
+///
+/// The buffer only grows when an input's worst-case compressed size exceeds
+/// it, so a long-lived `Compressor` (e.g. one per worker thread) stops
+/// allocating on the Rust side once it has seen its largest input. It is
+/// `Send` and `Sync`, and no C state outlives a call.
+///
+/// ```rust
+/// use lzsa_sys::{decompress, Compressor, Options};
+///
+/// let mut compressor = Compressor::new(Options::default());
+/// for chunk in [&b"first chunk, first chunk"[..], b"second chunk, second chunk"] {
+///     let compressed = compressor.compress(chunk)?;
+///     assert_eq!(decompress(compressed)?, chunk);
+/// }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct Compressor {
+    options: Options,
+    buffer: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl Compressor {
+    /// Create a compressor with an empty buffer
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Get the options used for compression
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Compress `input`, returning the compressed data borrowed from the
+    /// internal buffer
+    pub fn compress(&mut self, input: &[u8]) -> Result<&[u8]> {
+        let max_size = max_compressed_size_for(input.len(), &self.options);
+        if self.buffer.len() < max_size {
+            try_resize(&mut self.buffer, max_size)?;
+        }
+
+        let output_size = compress_into(input, &mut self.buffer, &self.options)?;
+        Ok(&self.buffer[..output_size])
+    }
+}
+
+/// Collects input pushed in pieces and compresses it as one stream at the
+/// end
+///
+/// [`finish`](Self::finish) compresses everything pushed in a single call,
+/// so the result is exactly what [`compress_with_options`] makes of the
+/// concatenation: one framed stream whose blocks can match across the
+/// boundaries between pushes. The tradeoff is memory: the whole input is
+/// held until `finish`, plus the compressed copy while it is made. For
+/// input that should not be held at once, use
+/// [`LzsaWriter`](stream::LzsaWriter), which compresses each block as it
+/// fills.
+///
+/// ```rust
+/// use lzsa_sys::{decompress, FrameSink, Options};
+///
+/// let mut sink = FrameSink::new(Options::default());
+/// for word in ["lazily ", "generated ", "lazily ", "generated"] {
+///     sink.push(word.as_bytes());
+/// }
+/// let compressed = sink.finish()?;
+/// assert_eq!(decompress(&compressed)?, b"lazily generated lazily generated");
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct FrameSink {
+    options: Options,
+    input: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl FrameSink {
+    /// Create an empty sink compressing with `options`
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            input: Vec::new(),
+        }
+    }
+
+    /// Append `chunk` to the input
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.input.extend_from_slice(chunk);
+    }
+
+    /// Number of input bytes pushed so far
+    pub fn len(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Whether nothing has been pushed yet
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    /// Compress everything pushed, with the errors of
+    /// [`compress_with_options`]
+    pub fn finish(self) -> Result<Vec<u8>> {
+        compress_with_options(&self.input, &self.options)
+    }
+}
+
+/// Compress data straight into a writer, returning the bytes written
+///
+/// The data is compressed into a scratch buffer and then written out with
+/// `write_all`, so the writer may take it in several chunks. Write failures
+/// are returned as `Error::Io`.
+#[cfg(feature = "std")]
+pub fn compress_to_writer<W: std::io::Write>(
+    input: &[u8],
+    writer: &mut W,
+    options: &Options,
+) -> Result<usize> {
+    let compressed = compress_with_options(input, options)?;
+    writer.write_all(&compressed)?;
+    Ok(compressed.len())
+}
+
+/// Compress large inputs on several threads
+///
+/// `input` is split into chunks of `block_size` bytes that are compressed
+/// independently on the rayon thread pool. Each chunk becomes a complete
+/// framed stream and the streams are concatenated in order, so the result is
+/// read back with [`decompress_all`] (plain [`decompress`] stops after the
+/// first chunk).
+///
+/// Matches cannot cross chunk boundaries, so the ratio is slightly worse
+/// than [`compress_with_options`], and each chunk adds 6 bytes of stream
+/// header and footer. Larger chunks lose less; a few MiB per chunk keeps
+/// the loss small while leaving enough chunks to spread across cores.
+/// Returns `Error::InvalidBlockSize` if `block_size` is 0 and
+/// `Error::InvalidMode` for raw modes, which have no frames.
+#[cfg(feature = "rayon")]
+pub fn compress_parallel(input: &[u8], options: &Options, block_size: usize) -> Result<Vec<u8>> {
+    use rayon::prelude::*;
+
+    if block_size == 0 {
+        return Err(Error::InvalidBlockSize);
+    }
+    if options.mode != Mode::Normal {
+        return Err(Error::InvalidMode);
+    }
+
+    let streams = input
+        .par_chunks(block_size)
+        .map(|chunk| compress_with_options(chunk, options))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(streams.concat())
+}
+
+/// Compress each of `inputs` on its own, keeping every result
+///
+/// Element `i` of the result is [`compress_with_options`] of `inputs[i]`,
+/// so an input that fails (e.g. a raw block over 64 KiB) gets its error in
+/// place and the rest are still compressed. Unlike [`compress_parallel`],
+/// every input is a separate whole stream. With the `rayon` feature the
+/// inputs are spread across the rayon thread pool; the order of the results
+/// is the same either way.
+#[cfg(feature = "alloc")]
+pub fn compress_batch(inputs: &[&[u8]], options: &Options) -> Vec<Result<Vec<u8>>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+
+        inputs
+            .par_iter()
+            .map(|input| compress_with_options(input, options))
+            .collect()
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        inputs
+            .iter()
+            .map(|input| compress_with_options(input, options))
+            .collect()
+    }
+}
+
+/// Compress on tokio's blocking thread pool
+///
+/// Runs [`compress_with_options`] through `tokio::task::spawn_blocking`, so
+/// CPU-bound compression does not stall the runtime's worker threads. The
+/// input is owned to satisfy the `'static` bound of the blocking task. Must
+/// be awaited within a tokio runtime; a panic in the task is resumed here.
+#[cfg(feature = "tokio")]
+pub async fn compress_async(input: Vec<u8>, options: Options) -> Result<Vec<u8>> {
+    spawn_blocking(move || compress_with_options(&input, &options)).await
+}
+
+/// Decompress on tokio's blocking thread pool
+///
+/// The [`decompress`] counterpart of [`compress_async`].
+#[cfg(feature = "tokio")]
+pub async fn decompress_async(input: Vec<u8>) -> Result<Vec<u8>> {
+    spawn_blocking(move || decompress(&input)).await
+}
+
+#[cfg(feature = "tokio")]
+async fn spawn_blocking<T, F>(task: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(task).await {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        // The runtime is shutting down and dropped the task unrun
+        Err(_) => Err(Error::Io(std::io::ErrorKind::Interrupted)),
+    }
+}
+
+/// Compress `new_data` as a complete framed stream and append it to
+/// `existing`
+///
+/// The bytes already in `existing` are left untouched, also on error, so an
+/// append-only log can grow one entry at a time and be read back with
+/// [`decompress_all`]. Empty `new_data` appends nothing. Returns
+/// `Error::InvalidMode` for raw modes, which `decompress_all` cannot read.
+///
+/// ```rust
+/// use lzsa_sys::{append_frame, decompress_all, Options};
+///
+/// let mut log = Vec::new();
+/// append_frame(&mut log, b"first entry", &Options::default())?;
+/// append_frame(&mut log, b", second entry", &Options::default())?;
+/// assert_eq!(decompress_all(&log)?, b"first entry, second entry");
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn append_frame(existing: &mut Vec<u8>, new_data: &[u8], options: &Options) -> Result<()> {
+    if options.mode != Mode::Normal {
+        return Err(Error::InvalidMode);
+    }
+
+    let start = existing.len();
+    try_resize(
+        existing,
+        start + max_compressed_size_for(new_data.len(), options),
+    )?;
+
+    match compress_into(new_data, &mut existing[start..], options) {
+        Ok(output_size) => {
+            existing.truncate(start + output_size);
+            Ok(())
+        }
+        Err(error) => {
+            existing.truncate(start);
+            Err(error)
+        }
+    }
+}
+
+/// Bytes from the start of the input that [`estimate_ratio`] compresses
+#[cfg(feature = "alloc")]
+const ESTIMATE_SAMPLE_SIZE: usize = BLOCK_SIZE;
+
+/// Estimate the compression ratio of `sample` without compressing all of it
+///
+/// Only the first 64 KiB are compressed, and the compressed-to-original size
+/// of that prefix is returned as the estimate for the whole input, from 0.0
+/// (compresses away) to 1.0 (incompressible, e.g. already compressed data).
+/// Input that is not homogeneous may compress differently past the sample.
+/// Empty input returns 1.0.
+#[cfg(feature = "alloc")]
+pub fn estimate_ratio(sample: &[u8], options: &Options) -> Result<f32> {
+    let prefix = &sample[..sample.len().min(ESTIMATE_SAMPLE_SIZE)];
+    if prefix.is_empty() {
+        return Ok(1.0);
+    }
+
+    let compressed = compress_with_options(prefix, options)?;
+    Ok((compressed.len() as f32 / prefix.len() as f32).min(1.0))
+}
+
+/// Get the size [`compress_with_options`] would produce, without keeping
+/// the output
+///
+/// The input is really compressed, into a scratch buffer that is dropped
+/// before returning, so the size is exact and the errors are the same.
+/// Only the length comes back, for measuring many inputs without collecting
+/// their output.
+#[cfg(feature = "alloc")]
+pub fn compressed_size(input: &[u8], options: &Options) -> Result<usize> {
+    if input.is_empty() {
+        return Ok(0);
+    }
+
+    let mut scratch = try_zeroed(max_compressed_size_for(input.len(), options))?;
+    compress_into(input, &mut scratch, options)
+}
+
+/// Check whether compressing `input` would fail to make it smaller
+///
+/// Returns true when the output of [`compress_with_options`] would be at
+/// least as large as `input`, empty input included. The size is exact, from
+/// [`compressed_size`], so nothing is kept for the caller to hold alongside
+/// `input`. Use [`estimate_ratio`] for a cheaper guess from a sample.
+#[cfg(feature = "alloc")]
+pub fn would_expand(input: &[u8], options: &Options) -> Result<bool> {
+    Ok(compressed_size(input, options)? >= input.len())
+}
+
+/// Get the exact compression ratio of `input`: compressed size over input
+/// size
+///
+/// The size comes from [`compressed_size`], so no output is kept. Unlike
+/// [`estimate_ratio`] the whole input is compressed and the result is not
+/// capped: incompressible data gives slightly more than 1.0, for the
+/// framing. Empty input returns 1.0, as it does there.
+#[cfg(feature = "alloc")]
+pub fn ratio(input: &[u8], options: &Options) -> Result<f64> {
+    if input.is_empty() {
+        return Ok(1.0);
+    }
+
+    Ok(compressed_size(input, options)? as f64 / input.len() as f64)
+}
+
+/// Marker byte in front of a framed stream written by [`compress_or_store`]
+#[cfg(feature = "alloc")]
+const MARKER_COMPRESSED: u8 = 0xc0;
+
+/// Marker byte in front of stored data that itself starts with a marker
+#[cfg(feature = "alloc")]
+const MARKER_STORED: u8 = 0xc1;
+
+/// Compress `input`, or keep it as-is if compressing does not make it smaller
+///
+/// Compressed data is a `0xC0` marker byte followed by a framed stream.
+/// Stored data is `input` itself, borrowed without copying, unless it starts
+/// with `0xC0` or `0xC1`; it is then escaped with a leading `0xC1`. The
+/// result is always read back by [`decompress_or_stored`], whichever path
+/// was taken. Only `Mode::Normal` is supported; raw modes return
+/// `Error::InvalidMode`.
+#[cfg(feature = "alloc")]
+pub fn compress_or_store<'a>(
+    input: &'a [u8],
+    options: &Options,
+) -> Result<alloc::borrow::Cow<'a, [u8]>> {
+    use alloc::borrow::Cow;
+
+    if options.mode != Mode::Normal {
+        return Err(Error::InvalidMode);
+    }
+
+    let compressed = compress_with_options(input, options)?;
+    if compressed.len() + 1 < input.len() {
+        let mut output = Vec::with_capacity(compressed.len() + 1);
+        output.push(MARKER_COMPRESSED);
+        output.extend_from_slice(&compressed);
+        return Ok(Cow::Owned(output));
+    }
+
+    match input.first() {
+        Some(&MARKER_COMPRESSED | &MARKER_STORED) => {
+            let mut output = Vec::with_capacity(input.len() + 1);
+            output.push(MARKER_STORED);
+            output.extend_from_slice(input);
+            Ok(Cow::Owned(output))
         }
+        _ => Ok(Cow::Borrowed(input)),
+    }
+}
+
+/// Read back data made by [`compress_or_store`]
+///
+/// Stored data is returned borrowed from `input`; only compressed data is
+/// decompressed into a new buffer.
+#[cfg(feature = "alloc")]
+pub fn decompress_or_stored(input: &[u8]) -> Result<alloc::borrow::Cow<'_, [u8]>> {
+    use alloc::borrow::Cow;
+
+    match input.split_first() {
+        Some((&MARKER_COMPRESSED, stream)) => decompress(stream).map(Cow::Owned),
+        Some((&MARKER_STORED, stored)) => Ok(Cow::Borrowed(stored)),
+        _ => Ok(Cow::Borrowed(input)),
+    }
+}
+
+/// Marker byte in front of a tiny input stored by [`compress_smart`]; a
+/// framed stream never starts with it
+#[cfg(feature = "alloc")]
+const MARKER_TINY: u8 = 0x00;
 
-        let mut output = vec![0u8; max_size];
+/// Compress `input`, storing it behind a 1-byte marker if it is too small
+/// to ever shrink
+///
+/// Every non-empty framed stream is at least [`MIN_STREAM_SIZE`] bytes, so
+/// inputs shorter than that always come out larger than they went in.
+/// Those are stored as a `0x00` marker followed by the input, at most
+/// `MIN_STREAM_SIZE` bytes in all, which is never more than compressing
+/// them would produce. Larger inputs are compressed into a plain framed
+/// stream, as [`compress_with_options`] does; empty input stays empty. Read
+/// the result back with [`decompress_smart`]. Only `Mode::Normal` is
+/// supported; raw modes return `Error::InvalidMode`.
+#[cfg(feature = "alloc")]
+pub fn compress_smart(input: &[u8], options: &Options) -> Result<Vec<u8>> {
+    if options.mode != Mode::Normal {
+        return Err(Error::InvalidMode);
+    }
+
+    if input.is_empty() || input.len() >= MIN_STREAM_SIZE {
+        return compress_with_options(input, options);
+    }
+
+    let mut output = Vec::with_capacity(input.len() + 1);
+    output.push(MARKER_TINY);
+    output.extend_from_slice(input);
+    Ok(output)
+}
+
+/// Read back data made by [`compress_smart`]
+///
+/// Stored tiny inputs are copied out from behind their marker; anything
+/// else is decompressed as a framed stream.
+#[cfg(feature = "alloc")]
+pub fn decompress_smart(input: &[u8]) -> Result<Vec<u8>> {
+    match input.split_first() {
+        Some((&MARKER_TINY, stored)) if stored.len() < MIN_STREAM_SIZE => Ok(stored.to_vec()),
+        Some((&MARKER_TINY, _)) => Err(Error::InvalidFormat),
+        _ => decompress(input),
+    }
+}
+
+/// Compress with LZSA1 (convenience function)
+#[cfg(all(feature = "alloc", feature = "v1"))]
+pub fn compress_v1(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    unsafe {
+        let max_size = max_compressed_size(input.len(), Version::V1, Mode::Normal);
+        let mut output = try_zeroed(max_size)?;
         let mut output_size = max_size;
 
-        let result = lzsa_decompress_v1(
+        let result = lzsa_compress_v1(
             input.as_ptr(),
             input.len(),
             output.as_mut_ptr(),
             &mut output_size,
         );
 
-        if result != 0 {
-            return Err(Error::from(result));
-        }
-
-        output.truncate(output_size);
+        output.truncate(checked_compress(result, output_size, output.len())?);
         Ok(output)
     }
 }
 
-/// Decompress LZSA2 data
-pub fn decompress_v2(input: &[u8]) -> Result<Vec<u8>> {
+/// Compress with LZSA2 (convenience function)
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub fn compress_v2(input: &[u8]) -> Result<Vec<u8>> {
     if input.is_empty() {
         return Ok(Vec::new());
     }
 
     unsafe {
-        let max_size = lzsa_get_max_decompressed_size(input.as_ptr(), input.len());
-
-        if max_size == 0 {
-            return Err(Error::DecompressionFailed);
-        }
-
-        let mut output = vec![0u8; max_size];
+        let max_size = max_compressed_size(input.len(), Version::V2, Mode::Normal);
+        let mut output = try_zeroed(max_size)?;
         let mut output_size = max_size;
 
-        let result = lzsa_decompress_v2(
+        let result = lzsa_compress_v2(
             input.as_ptr(),
             input.len(),
             output.as_mut_ptr(),
             &mut output_size,
         );
 
-        if result != 0 {
-            return Err(Error::from(result));
+        output.truncate(checked_compress(result, output_size, output.len())?);
+        Ok(output)
+    }
+}
+
+/// Compress data, reporting progress after each block
+///
+/// `progress` is called with the number of input bytes compressed so far
+/// and the total after every block of `options.block_size` bytes, so a
+/// progress bar advances steadily through large inputs; the last call is
+/// `(input.len(), input.len())`. It is never called after an error, nor for
+/// empty input. The result decompresses like that of
+/// [`compress_with_options`] and matches what
+/// [`LzsaWriter`](stream::LzsaWriter) writes for the same options. Raw
+/// modes are a single block, reported once when done.
+#[cfg(feature = "alloc")]
+pub fn compress_with_progress(
+    input: &[u8],
+    options: &Options,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<Vec<u8>> {
+    if options.mode != Mode::Normal {
+        let output = compress_with_options(input, options)?;
+        if !input.is_empty() {
+            progress(input.len(), input.len());
         }
+        return Ok(output);
+    }
 
-        output.truncate(output_size);
-        Ok(output)
+    let block_size = framed_block_size(options)?;
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut output = Vec::new();
+    push_stream_header(&mut output, options.version)?;
+
+    let mut previous_len = 0;
+    for (index, block) in input.chunks(block_size).enumerate() {
+        let start = index * block_size;
+        // The previous block directly precedes this one in the input
+        let window = &input[start - previous_len..start + block.len()];
+        push_block(&mut output, window, previous_len, options)?;
+
+        previous_len = block.len();
+        progress(start + block.len(), input.len());
     }
+
+    push_stream_footer(&mut output)?;
+    Ok(output)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Compress several slices as if they were one, without joining them first
+///
+/// The result is the same as compressing the concatenation of `chunks` with
+/// [`compress_with_progress`], and decompresses to that concatenation. Each
+/// block is gathered into a scratch buffer holding it and the block before
+/// (the match window), so memory use stays at two blocks however large the
+/// input; empty chunks are skipped. Raw modes are a single block and are
+/// joined before compressing.
+#[cfg(feature = "alloc")]
+pub fn compress_vectored(chunks: &[&[u8]], options: &Options) -> Result<Vec<u8>> {
+    if options.mode != Mode::Normal {
+        return compress_with_options(&chunks.concat(), options);
+    }
 
-    #[test]
-    fn test_compress_decompress_v1() {
-        let original = b"Hello, world! This is a test.";
-        let compressed = compress_v1(original).unwrap();
-        let decompressed = decompress(&compressed).unwrap();
-        assert_eq!(original, decompressed.as_slice());
+    let block_size = framed_block_size(options)?;
+    if chunks.iter().all(|chunk| chunk.is_empty()) {
+        return Ok(Vec::new());
     }
 
-    #[test]
-    fn test_compress_decompress_v2() {
-        let original = b"Hello, world! This is a test.";
-        let compressed = compress_v2(original).unwrap();
-        let decompressed = decompress(&compressed).unwrap();
-        assert_eq!(original, decompressed.as_slice());
+    let mut output = Vec::new();
+    push_stream_header(&mut output, options.version)?;
+
+    let mut pieces = chunks.iter().copied();
+    let mut current: &[u8] = &[];
+    let mut window = Vec::with_capacity(2 * block_size);
+    let mut previous_len = 0;
+    loop {
+        while window.len() - previous_len < block_size {
+            if current.is_empty() {
+                match pieces.next() {
+                    Some(chunk) => current = chunk,
+                    None => break,
+                }
+            }
+            let take = current
+                .len()
+                .min(block_size - (window.len() - previous_len));
+            window.extend_from_slice(&current[..take]);
+            current = &current[take..];
+        }
+
+        let block_len = window.len() - previous_len;
+        if block_len == 0 {
+            break;
+        }
+        push_block(&mut output, &window, previous_len, options)?;
+
+        // This block is the history of the next one
+        window.drain(..previous_len);
+        previous_len = block_len;
     }
 
-    #[test]
-    fn test_custom_options() {
-        let original = b"Custom options test data";
-        let options = Options {
-            version: Version::V2,
-            mode: Mode::Normal,
-            quality: Quality::Speed,
-            min_match_size: 3,
+    push_stream_footer(&mut output)?;
+    Ok(output)
+}
+
+/// Check the block size of framed compression done one block at a time
+#[cfg(feature = "alloc")]
+fn framed_block_size(options: &Options) -> Result<usize> {
+    let block_size = options.block_size as usize;
+    if block_size == 0 || block_size > BLOCK_SIZE {
+        return Err(Error::InvalidBlockSize);
+    }
+
+    Ok(block_size)
+}
+
+/// Append a stream header for `version` to `output`
+#[cfg(feature = "alloc")]
+fn push_stream_header(output: &mut Vec<u8>, version: Version) -> Result<()> {
+    let out_pos = output.len();
+    output.resize(out_pos + HEADER_SIZE, 0);
+    let mut header_size = HEADER_SIZE;
+    let result = unsafe {
+        lzsa_encode_stream_header(
+            output[out_pos..].as_mut_ptr(),
+            &mut header_size,
+            version as c_int,
+        )
+    };
+    output.truncate(out_pos + checked_compress(result, header_size, HEADER_SIZE)?);
+    Ok(())
+}
+
+/// Compress the end of `window` as one framed block appended to `output`;
+/// its first `previous_len` bytes are the previous block, used as history
+#[cfg(feature = "alloc")]
+fn push_block(
+    output: &mut Vec<u8>,
+    window: &[u8],
+    previous_len: usize,
+    options: &Options,
+) -> Result<()> {
+    let block_len = window.len() - previous_len;
+    let out_pos = output.len();
+    output.resize(out_pos + FRAME_SIZE + block_len, 0);
+    let output_size = traced!(
+        "compress_block",
+        options.version,
+        options.mode,
+        block_len,
+        {
+            let mut output_size = output.len() - out_pos;
+            let result = unsafe {
+                lzsa_compress_block(
+                    window.as_ptr(),
+                    previous_len,
+                    block_len,
+                    output[out_pos..].as_mut_ptr(),
+                    &mut output_size,
+                    options,
+                )
+            };
+            checked_compress(result, output_size, output.len() - out_pos)
+        }
+    )?;
+    output.truncate(out_pos + output_size);
+    Ok(())
+}
+
+/// Append the end-of-stream footer to `output`
+#[cfg(feature = "alloc")]
+fn push_stream_footer(output: &mut Vec<u8>) -> Result<()> {
+    let out_pos = output.len();
+    output.resize(out_pos + FRAME_SIZE, 0);
+    let mut footer_size = FRAME_SIZE;
+    let result =
+        unsafe { lzsa_encode_stream_footer(output[out_pos..].as_mut_ptr(), &mut footer_size) };
+    output.truncate(out_pos + checked_compress(result, footer_size, FRAME_SIZE)?);
+    Ok(())
+}
+
+/// Compress data and report how it compressed
+///
+/// The output is a framed stream like [`compress_with_options`] produces.
+/// Only `Mode::Normal` is supported; raw modes return `Error::InvalidMode`.
+///
+/// ```rust
+/// use lzsa_sys::{compress_with_stats, Options};
+///
+/// let input = b"abcabcabcabcabcabcabcabcabcabcabcabc";
+/// let (compressed, stats) = compress_with_stats(input, &Options::default())?;
+/// assert_eq!(stats.input_len, input.len());
+/// assert_eq!(stats.output_len, compressed.len());
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn compress_with_stats(input: &[u8], options: &Options) -> Result<(Vec<u8>, Stats)> {
+    unsafe {
+        let max_size = max_compressed_size_for(input.len(), options);
+        let mut output = try_zeroed(max_size)?;
+        let mut output_size = max_size;
+        let mut raw = RawStats::default();
+
+        let size = traced!("compress", options.version, options.mode, input.len(), {
+            let result = lzsa_compress_with_stats(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+                options as *const Options,
+                &mut raw,
+            );
+            checked_compress(result, output_size, output.len())
+        })?;
+        output.truncate(size);
+
+        let stats = Stats {
+            input_len: raw.input_size,
+            output_len: raw.output_size,
+            block_count: raw.block_count,
+            command_count: raw.command_count,
+            match_count: raw.match_count,
+            literal_count: raw.literal_count,
         };
-        let compressed = compress_with_options(original, &options).unwrap();
-        let decompressed = decompress(&compressed).unwrap();
-        assert_eq!(original, decompressed.as_slice());
+        Ok((output, stats))
     }
+}
 
-    #[test]
-    fn test_empty_input() {
-        let empty: &[u8] = &[];
-        let compressed = compress_v1(empty).unwrap();
-        assert_eq!(compressed.len(), 0);
-        let decompressed = decompress(&compressed).unwrap();
-        assert_eq!(decompressed.len(), 0);
+/// Compress data, letting the first block reference a dictionary
+///
+/// Small inputs that resemble each other compress much better when each is
+/// primed with the same sample data. Only the last 64 KiB of `dictionary` are
+/// used, and it is not stored in the output: decompress with
+/// [`decompress_with_dictionary`] and the same dictionary. An empty dictionary
+/// is the same as [`compress_with_options`]. Raw modes have no frames to
+/// carry the history and return `Error::InvalidMode`.
+#[cfg(feature = "alloc")]
+pub fn compress_with_dictionary(
+    input: &[u8],
+    dictionary: &[u8],
+    options: &Options,
+) -> Result<Vec<u8>> {
+    if dictionary.is_empty() {
+        return compress_with_options(input, options);
+    }
+
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    unsafe {
+        let max_size = max_compressed_size_for(input.len(), options);
+        let mut output = try_zeroed(max_size)?;
+        let mut output_size = max_size;
+
+        let size = traced!("compress", options.version, options.mode, input.len(), {
+            let result = lzsa_compress_with_dictionary(
+                input.as_ptr(),
+                input.len(),
+                dictionary.as_ptr(),
+                dictionary.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+                options as *const Options,
+            );
+            checked_compress(result, output_size, output.len())
+        })?;
+        output.truncate(size);
+        Ok(output)
+    }
+}
+
+/// Get the maximum decompressed size of a framed LZSA stream
+///
+/// Only the frame headers are walked; no data is decompressed. The result is
+/// an upper bound (a full 64 KiB for each block), which makes it suitable for
+/// rejecting oversized inputs before allocating. A stream with no blocks
+/// returns 0. Returns `Error::InvalidFormat` if the frames cannot be parsed
+/// and `Error::Truncated` if the input ends before the footer.
+pub fn max_decompressed_size(compressed: &[u8]) -> Result<usize> {
+    check_framed_len(compressed)?;
+    check_truncated(compressed)?;
+    if empty_stream_version(compressed)?.is_some() {
+        return Ok(0);
+    }
+    let max_size = unsafe { lzsa_get_max_decompressed_size(compressed.as_ptr(), compressed.len()) };
+
+    if max_size == 0 {
+        return Err(Error::InvalidFormat);
+    }
+
+    Ok(max_size)
+}
+
+/// Read the format version from the stream header
+///
+/// Only the 3-byte header is looked at: nothing is allocated or
+/// decompressed, so this is cheaper than [`decompress_detect`] for routing
+/// data by format. Raw blocks have no header and return
+/// `Error::InvalidFormat`.
+pub fn detect_version(input: &[u8]) -> Result<Version> {
+    let mut version: c_int = 0;
+    let result = unsafe { lzsa_decode_stream_header(input.as_ptr(), input.len(), &mut version) };
+    if result != 0 {
+        return Err(Error::InvalidFormat);
+    }
+
+    Version::from_raw(version).ok_or(Error::InvalidVersion)
+}
+
+/// Read the stream header and the first block frame without decompressing
+///
+/// Raw blocks (`Mode::RawForward`/`Mode::RawBackward`) have no header and
+/// return `Error::InvalidFormat`, as does any other input that does not
+/// start with the LZSA magic bytes.
+///
+/// ```rust
+/// # #[cfg(feature = "v2")] {
+/// use lzsa_sys::{compress_v2, parse_frame_header, Version};
+///
+/// let header = parse_frame_header(&compress_v2(b"inspect me, inspect me")?)?;
+/// assert_eq!(header.version(), Version::V2);
+/// assert!(header.block_size.is_some());
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+pub fn parse_frame_header(input: &[u8]) -> Result<FrameHeader> {
+    let version = detect_version(input)?;
+
+    let (block_size, uncompressed) = match input.get(HEADER_SIZE..) {
+        Some(frame) if frame.len() >= FRAME_SIZE => decode_block_frame(frame)?,
+        _ => (0, false),
+    };
+
+    // A zero-sized frame is the footer, not a block
+    let block_size = (block_size != 0).then_some(block_size);
+    let original_size = match block_size {
+        None if input.len() >= HEADER_SIZE + FRAME_SIZE => Some(0),
+        Some(size) if uncompressed => {
+            let next = HEADER_SIZE + FRAME_SIZE + size;
+            match next_block_frame(input, next) {
+                Ok((0, _)) => Some(size),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    Ok(FrameHeader {
+        version,
+        uncompressed,
+        block_size,
+        original_size,
+    })
+}
+
+/// Find the offsets in `input` where a framed stream could start
+///
+/// Every position holding a stream header ([`FRAME_MAGIC`] and a version
+/// byte, see [`Version::frame_magic`]) is returned in order, for recovery
+/// tools that try [`decompress`] from each. Nothing past the header is
+/// checked, so candidates can be false positives (the bytes may occur in
+/// other data), and a stream may contain what looks like a header of its
+/// own.
+///
+/// ```rust
+/// # #[cfg(feature = "v2")] {
+/// use lzsa_sys::{compress_v2, decompress, find_frames};
+///
+/// let mut file = b"junk".to_vec();
+/// file.extend(compress_v2(b"recovered, recovered, recovered")?);
+/// let offsets = find_frames(&file);
+/// assert_eq!(offsets, [4]);
+/// assert_eq!(decompress(&file[offsets[0]..])?, b"recovered, recovered, recovered");
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn find_frames(input: &[u8]) -> Vec<usize> {
+    input
+        .windows(HEADER_SIZE)
+        .enumerate()
+        .filter(|(_, header)| {
+            [Version::V1, Version::V2]
+                .iter()
+                .any(|version| version.frame_magic() == *header)
+        })
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Check whether `input` looks like a framed LZSA stream
+///
+/// The magic bytes, the format version and the first block frame are
+/// checked, and the first block must fit in the input; nothing is
+/// decompressed or allocated. Empty, short and raw-block inputs return
+/// `false`. A `true` result does not guarantee [`decompress`] succeeds.
+pub fn is_lzsa(input: &[u8]) -> bool {
+    if check_framed_len(input).is_err() || detect_version(input).is_err() {
+        return false;
+    }
+
+    match decode_block_frame(&input[HEADER_SIZE..]) {
+        Ok((block_size, _)) => {
+            block_size <= BLOCK_SIZE && block_size <= input.len() - HEADER_SIZE - FRAME_SIZE
+        }
+        Err(_) => false,
+    }
+}
+
+/// Parse the block frame at `pos` in `input`, returning `Error::Truncated`
+/// if the input ends before it
+fn next_block_frame(input: &[u8], pos: usize) -> Result<(usize, bool)> {
+    match input.get(pos..) {
+        Some(frame) if frame.len() >= FRAME_SIZE => decode_block_frame(frame),
+        _ => Err(Error::Truncated),
+    }
+}
+
+/// Parse the block frame at the start of `input` into its data size and
+/// whether the block is stored uncompressed
+fn decode_block_frame(input: &[u8]) -> Result<(usize, bool)> {
+    let mut block_size = 0usize;
+    let mut is_uncompressed: c_int = 0;
+
+    let result = unsafe {
+        lzsa_decode_block_frame(
+            input.as_ptr(),
+            input.len(),
+            &mut block_size,
+            &mut is_uncompressed,
+        )
+    };
+    if result != 0 {
+        return Err(Error::InvalidFormat);
+    }
+
+    Ok((block_size, is_uncompressed != 0))
+}
+
+/// Return `Error::Truncated` if `input` ends before a block or the footer
+/// its frames announce
+///
+/// Any other damage passes, leaving the C side to report it as before.
+fn check_truncated(input: &[u8]) -> Result<()> {
+    match framed_stream_len(input) {
+        Err(Error::Truncated) => Err(Error::Truncated),
+        _ => Ok(()),
+    }
+}
+
+/// Reject framed input too short to hold a stream header and footer before
+/// handing it to the C side
+fn check_framed_len(input: &[u8]) -> Result<()> {
+    if input.len() < HEADER_SIZE + FRAME_SIZE {
+        return Err(Error::InvalidFormat);
+    }
+
+    Ok(())
+}
+
+/// Recognize a framed stream with no blocks: a header followed directly by
+/// the footer
+///
+/// It is valid and decodes to nothing, but the C side reports its maximum
+/// decompressed size as 0, which is also how it reports an error. The header
+/// is still validated, so only a real stream returns its version.
+fn empty_stream_version(input: &[u8]) -> Result<Option<Version>> {
+    if input.len() != HEADER_SIZE + FRAME_SIZE || input[HEADER_SIZE..] != [0; FRAME_SIZE] {
+        return Ok(None);
+    }
+
+    detect_version(input).map(Some)
+}
+
+/// Decompress data (auto-detects LZSA1 or LZSA2)
+///
+/// Input that ends before a block or the footer its frames announce, such
+/// as a partially downloaded file, returns `Error::Truncated` without being
+/// decoded; the other decompressors of framed streams check for it too.
+///
+/// Empty input returns an empty `Vec` without being looked at. A stream with
+/// no blocks, a header directly followed by the footer, also decompresses to
+/// nothing, but only once its header has been validated.
+#[cfg(feature = "alloc")]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    decompress_to_vec(input).map(|(output, _)| output)
+}
+
+/// Decompress data (auto-detects LZSA1 or LZSA2) into at most `max_output`
+/// bytes, for untrusted input
+///
+/// [`decompress`] sizes its buffer from the frame headers, 64 KiB for every
+/// block however little data the block holds, so a short hostile input can
+/// still ask for about 16 KiB of output per input byte. Here nothing larger
+/// than `max_output` is allocated or produced: data that does not fit
+/// returns `Error::OutputTooLarge`. Stored blocks declare their exact size,
+/// so input whose stored blocks alone exceed the limit is rejected before
+/// allocating; everything else is caught during decoding. Corrupt data
+/// still returns `Error::DecompressionFailed`, even when it would not have
+/// fit either.
+#[cfg(feature = "alloc")]
+pub fn decompress_limited(input: &[u8], max_output: usize) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let max_size = max_decompressed_size(input)?;
+    if max_size == 0 {
+        return Ok(Vec::new());
+    }
+    if max_size > max_output && stored_len(input) > max_output {
+        return Err(Error::OutputTooLarge);
+    }
+
+    let mut output = try_zeroed(max_size.min(max_output))?;
+    match decompress_inner(input, &mut output) {
+        Ok((output_size, _)) => {
+            output.truncate(output_size);
+            Ok(output)
+        }
+        Err(Error::DecompressionFailed)
+            if max_output < max_size && exceeds_output(input, max_output) =>
+        {
+            Err(Error::OutputTooLarge)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Total size of the stored blocks of the framed stream at the start of
+/// `input`, read from their frames
+///
+/// Stored blocks decode to exactly their size, so this is a lower bound on
+/// the decompressed size. Frames are read up to the footer or the first
+/// malformed one.
+fn stored_len(input: &[u8]) -> usize {
+    let mut stored = 0;
+    let mut pos = HEADER_SIZE;
+    while let Some(Ok((block_size, uncompressed))) = input.get(pos..).map(decode_block_frame) {
+        if block_size == 0 {
+            break;
+        }
+        if uncompressed {
+            stored += block_size;
+        }
+        pos += FRAME_SIZE + block_size;
+    }
+    stored
+}
+
+/// Whether the framed stream at the start of `input` decodes to more than
+/// `limit` bytes
+///
+/// The C decompressor reports running out of room and corrupt data as the
+/// same failure. This decodes the stream again one block at a time, each
+/// with a full block of room, so only two blocks are held whatever the
+/// limit; data that fails to decode before passing the limit is corrupt.
+#[cfg(feature = "alloc")]
+fn exceeds_output(input: &[u8], limit: usize) -> bool {
+    let Ok(len) = framed_stream_len(input) else {
+        return false;
+    };
+
+    let mut total = 0;
+    for block in blocks(&input[..len]) {
+        let Ok(block) = block else {
+            return false;
+        };
+        total += block.len();
+        if total > limit {
+            return true;
+        }
+    }
+    false
+}
+
+/// Decompress data and report which format it was in
+///
+/// Returns `Error::InvalidFormat` for empty input, which has no version, and
+/// `Error::InvalidVersion` if the C side reports an unknown version.
+#[cfg(feature = "alloc")]
+pub fn decompress_detect(input: &[u8]) -> Result<(Vec<u8>, Version)> {
+    if input.is_empty() {
+        return Err(Error::InvalidFormat);
+    }
+
+    let (output, version) = decompress_to_vec(input)?;
+    let version = Version::from_raw(version).ok_or(Error::InvalidVersion)?;
+    Ok((output, version))
+}
+
+/// Decompress data along with the version the C side detected
+///
+/// Like [`decompress_detect`], but a version outside the known ones is
+/// returned as `None` with the data rather than as `Error::InvalidVersion`,
+/// so everything `lzsa_decompress` reports is kept. Empty input has no
+/// version and returns an empty `Vec` with `None`.
+#[cfg(feature = "alloc")]
+pub fn decompress_with_version_out(input: &[u8]) -> Result<(Vec<u8>, Option<Version>)> {
+    if input.is_empty() {
+        return Ok((Vec::new(), None));
+    }
+
+    let (output, version) = decompress_to_vec(input)?;
+    Ok((output, Version::from_raw(version)))
+}
+
+/// Compress a string; read it back with [`decompress_to_string`]
+#[cfg(feature = "alloc")]
+pub fn compress_str(s: &str, options: &Options) -> Result<Vec<u8>> {
+    compress_with_options(s.as_bytes(), options)
+}
+
+/// Decompress data (auto-detects LZSA1 or LZSA2) into a `String`
+///
+/// Returns `Error::InvalidUtf8` if the decompressed bytes are not valid
+/// UTF-8; they are never replaced or dropped.
+#[cfg(feature = "alloc")]
+pub fn decompress_to_string(input: &[u8]) -> Result<alloc::string::String> {
+    alloc::string::String::from_utf8(decompress(input)?).map_err(|_| Error::InvalidUtf8)
+}
+
+/// Decompress data made by [`compress_with_dictionary`]
+///
+/// `dictionary` must be the one the data was compressed with. The stream does
+/// not record it, so a different dictionary either fails with
+/// `Error::DecompressionFailed` or decodes to the wrong bytes.
+#[cfg(feature = "alloc")]
+pub fn decompress_with_dictionary(input: &[u8], dictionary: &[u8]) -> Result<Vec<u8>> {
+    if dictionary.is_empty() {
+        return decompress(input);
+    }
+
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    check_framed_len(input)?;
+    check_truncated(input)?;
+    if empty_stream_version(input)?.is_some() {
+        return Ok(Vec::new());
+    }
+
+    unsafe {
+        let max_size = lzsa_get_max_decompressed_size(input.as_ptr(), input.len());
+
+        if max_size == 0 {
+            return Err(Error::DecompressionFailed);
+        }
+
+        let mut output = try_zeroed(max_size)?;
+        let mut output_size = max_size;
+
+        let size = traced!(
+            "decompress",
+            detect_version(input).ok(),
+            Mode::Normal,
+            input.len(),
+            {
+                let result = lzsa_decompress_with_dictionary(
+                    input.as_ptr(),
+                    input.len(),
+                    dictionary.as_ptr(),
+                    dictionary.len(),
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    core::ptr::null_mut(),
+                );
+                checked_decompress(result, output_size, output.len())
+            }
+        )?;
+        output.truncate(size);
+        Ok(output)
+    }
+}
+
+/// Decompress a file made of several concatenated framed streams
+///
+/// Each stream (header, blocks, footer) is decompressed in turn and the
+/// results are appended. Bytes after the last stream that do not start a
+/// valid stream return `Error::InvalidFormat`.
+#[cfg(feature = "alloc")]
+pub fn decompress_all(input: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let stream_len = framed_stream_len(&input[pos..])?;
+        output.extend_from_slice(&decompress(&input[pos..pos + stream_len])?);
+        pos += stream_len;
+    }
+
+    Ok(output)
+}
+
+/// Iterate over the decoded blocks of one or more concatenated framed
+/// streams
+///
+/// Each item is the data of one block frame (up to 64 KiB), decoded only
+/// when the iterator is advanced, so progress can be reported and each
+/// block dropped once handled. Only the previous block is kept, as the
+/// match window for the next one. Iteration stops at the end of `input`;
+/// malformed input yields one final `Err` (`Error::InvalidFormat` for bad
+/// framing, `Error::Truncated` for a stream cut short).
+///
+/// ```rust
+/// # #[cfg(feature = "v2")] {
+/// use lzsa_sys::{blocks, compress_v2};
+///
+/// let data = vec![42u8; 200_000];
+/// let compressed = compress_v2(&data)?;
+///
+/// let mut done = 0;
+/// for block in blocks(&compressed) {
+///     done += block?.len();
+/// }
+/// assert_eq!(done, data.len());
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn blocks(input: &[u8]) -> BlockIter<'_> {
+    BlockIter {
+        input,
+        pos: 0,
+        version: None,
+        window: Vec::new(),
+        failed: false,
+    }
+}
+
+/// Decompress one or more concatenated framed streams as far as they go,
+/// for diagnosing corrupt data
+///
+/// Returns the data decoded before the first failure together with that
+/// error, or all of it and `None`. Decoding happens a block frame at a time
+/// (see [`blocks`]), so the output ends at the last good block: the bytes of
+/// the failing block are not included. [`decompress_all`] stays the strict
+/// way to read the same input.
+///
+/// ```rust
+/// # #[cfg(feature = "v1")] {
+/// use lzsa_sys::{compress_v1, decompress_partial, Error};
+///
+/// let data = vec![7u8; 100_000];
+/// let compressed = compress_v1(&data)?;
+///
+/// // Every block arrived, but the footer did not
+/// let (output, error) = decompress_partial(&compressed[..compressed.len() - 3]);
+/// assert_eq!(output, data);
+/// assert_eq!(error, Some(Error::Truncated));
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_partial(input: &[u8]) -> (Vec<u8>, Option<Error>) {
+    let mut output = Vec::new();
+    for block in blocks(input) {
+        match block {
+            Ok(data) => output.extend_from_slice(&data),
+            Err(error) => return (output, Some(error)),
+        }
+    }
+
+    (output, None)
+}
+
+/// Decompress only the first `max_bytes` bytes of the data
+///
+/// Blocks are decoded one at a time with [`blocks`] until `max_bytes` of
+/// output are reached, and the rest of `input` is not decoded, nor even
+/// checked: reading a small header from a large stream costs one block (up
+/// to 64 KiB) of work, not the whole stream. Reaching the limit is not an
+/// error; data shorter than `max_bytes` is returned whole. Errors in the
+/// blocks that are decoded are returned as usual.
+///
+/// ```rust
+/// # #[cfg(feature = "v2")] {
+/// use lzsa_sys::{compress_v2, decompress_prefix};
+///
+/// let data: Vec<u8> = (0..500_000u32).map(|i| (i % 199) as u8).collect();
+/// let compressed = compress_v2(&data)?;
+/// assert_eq!(decompress_prefix(&compressed, 300)?, &data[..300]);
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_prefix(input: &[u8], max_bytes: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut blocks = blocks(input);
+    while output.len() < max_bytes {
+        match blocks.next() {
+            Some(block) => output.extend_from_slice(&block?),
+            None => break,
+        }
+    }
+
+    output.truncate(max_bytes);
+    Ok(output)
+}
+
+/// Decompress `input` a block at a time, handing each block to `sink`
+///
+/// The decode-side counterpart of [`compress_with_progress`]: only the
+/// current block and the one before it (the match window) are held, so a
+/// large stream can be written out or hashed without ever holding all of
+/// it. Each call gets one block frame's data, up to 64 KiB, in order; input
+/// is read as by [`blocks`]. An error from `sink` stops decoding and is
+/// returned as is, and a decoding error is returned after the blocks before
+/// it have been handed over.
+///
+/// ```rust
+/// # #[cfg(feature = "v2")] {
+/// use lzsa_sys::{compress_v2, decompress_streaming};
+///
+/// let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+/// let compressed = compress_v2(&data)?;
+///
+/// let mut sum = 0u64;
+/// decompress_streaming(&compressed, |block| {
+///     sum += block.iter().map(|&b| u64::from(b)).sum::<u64>();
+///     Ok(())
+/// })?;
+/// assert_eq!(sum, data.iter().map(|&b| u64::from(b)).sum::<u64>());
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decompress_streaming(input: &[u8], mut sink: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
+    for block in blocks(input) {
+        sink(&block?)?;
+    }
+    Ok(())
+}
+
+/// Iterator returned by [`blocks`]
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct BlockIter<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Version of the stream being read, `None` between streams
+    version: Option<Version>,
+    /// Previous decoded block of the current stream
+    window: Vec<u8>,
+    failed: bool,
+}
+
+#[cfg(feature = "alloc")]
+impl BlockIter<'_> {
+    fn next_block(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            let version = match self.version {
+                Some(version) => version,
+                None if self.pos == self.input.len() => return Ok(None),
+                None => {
+                    let version = detect_version(&self.input[self.pos..])?;
+                    self.pos += HEADER_SIZE;
+                    self.version = Some(version);
+                    version
+                }
+            };
+
+            let (block_size, uncompressed) = next_block_frame(self.input, self.pos)?;
+            self.pos += FRAME_SIZE;
+
+            // Footer: a following stream starts without history
+            if block_size == 0 {
+                self.version = None;
+                self.window.clear();
+                continue;
+            }
+
+            if block_size > BLOCK_SIZE {
+                return Err(Error::InvalidFormat);
+            }
+            let block = self
+                .input
+                .get(self.pos..self.pos + block_size)
+                .ok_or(Error::Truncated)?;
+            self.pos += block_size;
+
+            if uncompressed {
+                self.window.clear();
+                self.window.extend_from_slice(block);
+                return Ok(Some(block.to_vec()));
+            }
+
+            let previous_len = self.window.len();
+            self.window.resize(previous_len + BLOCK_SIZE, 0);
+            let window = &mut self.window;
+            let output_size = traced!("decompress_block", version, Mode::Normal, block.len(), {
+                let mut output_size = BLOCK_SIZE;
+                let result = unsafe {
+                    lzsa_decompress_block(
+                        block.as_ptr(),
+                        block.len(),
+                        window.as_mut_ptr(),
+                        previous_len,
+                        &mut output_size,
+                        version as c_int,
+                    )
+                };
+                checked_decompress(result, output_size, BLOCK_SIZE)
+            })?;
+            self.window.truncate(previous_len + output_size);
+            self.window.drain(..previous_len);
+            return Ok(Some(self.window.clone()));
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Iterator for BlockIter<'_> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let item = self.next_block().transpose();
+        self.failed = matches!(item, Some(Err(_)));
+        item
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::iter::FusedIterator for BlockIter<'_> {}
+
+/// Walk the frames of the framed stream at the start of `input` and return
+/// its length, footer included
+///
+/// Returns `Error::Truncated` if the input ends before a frame or block.
+fn framed_stream_len(input: &[u8]) -> Result<usize> {
+    let mut version: c_int = 0;
+    let result = unsafe { lzsa_decode_stream_header(input.as_ptr(), input.len(), &mut version) };
+    if result != 0 {
+        return Err(Error::InvalidFormat);
+    }
+
+    let mut pos = HEADER_SIZE;
+    loop {
+        let (block_size, _) = next_block_frame(input, pos)?;
+        pos += FRAME_SIZE;
+
+        if block_size == 0 {
+            return Ok(pos);
+        }
+
+        if block_size > input.len() - pos {
+            return Err(Error::Truncated);
+        }
+        pos += block_size;
+    }
+}
+
+/// Read one framed stream from a reader and decompress it
+///
+/// Exactly the bytes of the stream are read: the header, each block frame
+/// with its declared data size, and the footer. Anything after the footer is
+/// left in the reader. Read failures (including a stream cut short) are
+/// returned as `Error::Io`; a block frame larger than [`BLOCK_SIZE`] returns
+/// `Error::InvalidFormat`.
+#[cfg(feature = "std")]
+pub fn decompress_from_reader<R: std::io::Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut input = vec![0u8; HEADER_SIZE];
+    reader.read_exact(&mut input)?;
+    detect_version(&input)?;
+
+    loop {
+        let frame_start = input.len();
+        input.resize(frame_start + FRAME_SIZE, 0);
+        reader.read_exact(&mut input[frame_start..])?;
+
+        let (block_size, _) = decode_block_frame(&input[frame_start..])?;
+        if block_size == 0 {
+            break;
+        }
+        if block_size > BLOCK_SIZE {
+            return Err(Error::InvalidFormat);
+        }
+
+        let block_start = input.len();
+        input.resize(block_start + block_size, 0);
+        reader.read_exact(&mut input[block_start..])?;
+    }
+
+    decompress(&input)
+}
+
+/// Allocate a buffer of `len` zeroed bytes, returning `Error::OutOfMemory`
+/// instead of aborting if the allocation fails
+#[cfg(feature = "alloc")]
+fn try_zeroed(len: usize) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    try_resize(&mut buffer, len)?;
+    Ok(buffer)
+}
+
+/// Grow or shrink `buffer` to `len` bytes, zeroing new ones, returning
+/// `Error::OutOfMemory` (and leaving `buffer` as it was) if growing it fails
+#[cfg(feature = "alloc")]
+fn try_resize(buffer: &mut Vec<u8>, len: usize) -> Result<()> {
+    if let Some(additional) = len.checked_sub(buffer.len()) {
+        buffer
+            .try_reserve_exact(additional)
+            .map_err(|_| Error::OutOfMemory)?;
+    }
+    buffer.resize(len, 0);
+    Ok(())
+}
+
+/// Probe the decompressed size, then decompress into a new `Vec`
+#[cfg(feature = "alloc")]
+fn decompress_to_vec(input: &[u8]) -> Result<(Vec<u8>, c_int)> {
+    let mut output = Vec::new();
+    let version = decompress_to(input, &mut output)?;
+    Ok((output, version))
+}
+
+/// Probe the decompressed size, then decompress into `output`, replacing
+/// its contents and returning the version
+#[cfg(feature = "alloc")]
+fn decompress_to(input: &[u8], output: &mut Vec<u8>) -> Result<c_int> {
+    output.clear();
+    check_framed_len(input)?;
+    check_truncated(input)?;
+    if let Some(version) = empty_stream_version(input)? {
+        return Ok(version as c_int);
+    }
+    let max_size = unsafe { lzsa_get_max_decompressed_size(input.as_ptr(), input.len()) };
+
+    if max_size == 0 {
+        return Err(Error::DecompressionFailed);
+    }
+
+    try_resize(output, max_size)?;
+    let result = decompress_inner(input, output);
+    let (output_size, version) = result.inspect_err(|_| output.clear())?;
+    output.truncate(output_size);
+    Ok(version)
+}
+
+/// Reusable decompressor that keeps its output buffer between calls
+///
+/// The buffer is sized from the frame headers (64 KiB per block) and only
+/// grows when an input needs more, so consecutive inputs of similar size
+/// decompress without allocating. Like [`Compressor`] it is `Send` and
+/// `Sync`. The returned slice borrows the decompressor until the next call:
+///
+/// ```rust
+/// # #[cfg(feature = "v2")] {
+/// use lzsa_sys::{compress_v2, Decompressor, Version};
+///
+/// let chunks = vec![compress_v2(b"one, one, one")?, compress_v2(b"two, two, two")?];
+///
+/// let mut decompressor = Decompressor::new();
+/// let mut total = 0;
+/// for chunk in &chunks {
+///     total += decompressor.decompress(chunk)?.len();
+/// }
+/// assert_eq!(total, 26);
+/// assert_eq!(decompressor.version(), Some(Version::V2));
+/// # }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct Decompressor {
+    buffer: Vec<u8>,
+    version: Option<Version>,
+}
+
+#[cfg(feature = "alloc")]
+impl Decompressor {
+    /// Create a decompressor with an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Format version of the last non-empty input that decompressed
+    /// successfully
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Decompress `input` (auto-detects LZSA1 or LZSA2), returning the data
+    /// borrowed from the internal buffer
+    pub fn decompress(&mut self, input: &[u8]) -> Result<&[u8]> {
+        if input.is_empty() {
+            return Ok(&[]);
+        }
+        check_framed_len(input)?;
+        check_truncated(input)?;
+        if let Some(version) = empty_stream_version(input)? {
+            self.version = Some(version);
+            return Ok(&[]);
+        }
+
+        let max_size = unsafe { lzsa_get_max_decompressed_size(input.as_ptr(), input.len()) };
+
+        if max_size == 0 {
+            return Err(Error::DecompressionFailed);
+        }
+
+        if self.buffer.len() < max_size {
+            try_resize(&mut self.buffer, max_size)?;
+        }
+
+        let (output_size, version) = decompress_inner(input, &mut self.buffer)?;
+        self.version = Some(Version::from_raw(version).ok_or(Error::InvalidVersion)?);
+        Ok(&self.buffer[..output_size])
+    }
+}
+
+/// Reusable context that keeps the C compressor alive between calls
+///
+/// Every other compressing function, [`Compressor`] included, sets up the
+/// C compressor and its match-finding tables for each call and frees them
+/// afterwards. A context does that once in [`new`](Self::new), and also
+/// keeps the C-side copy of the input and the output buffer, so a stream of
+/// small messages compresses with no allocation once the largest one has
+/// been seen. Decompression has no C state to keep; it only reuses the
+/// buffers.
+///
+/// Only `Mode::Normal` is supported. The context owns its C handle and frees
+/// it exactly once when dropped; it cannot be cloned, so no other value can
+/// free it again:
+///
+/// ```compile_fail
+/// let context = lzsa_sys::Context::new(lzsa_sys::Options::default()).unwrap();
+/// let copy = context.clone();
+/// ```
+///
+/// ```rust
+/// use lzsa_sys::{Context, Options};
+///
+/// let mut context = Context::new(Options::default())?;
+/// for message in [&b"ping ping ping ping"[..], b"pong pong pong pong"] {
+///     let compressed = context.compress(message)?.to_vec();
+///     assert_eq!(context.decompress(&compressed)?, message);
+/// }
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct Context {
+    raw: core::ptr::NonNull<RawContext>,
+    options: Options,
+    buffer: Vec<u8>,
+}
+
+// SAFETY: the C context is exclusively owned, only used through `&mut self`,
+// and has no ties to the thread that created it
+#[cfg(feature = "alloc")]
+unsafe impl Send for Context {}
+#[cfg(feature = "alloc")]
+unsafe impl Sync for Context {}
+
+#[cfg(feature = "alloc")]
+impl Context {
+    /// Create a context compressing with `options`
+    ///
+    /// The options are validated here rather than on each call, with the
+    /// same errors as [`compress_with_options`]; raw modes return
+    /// `Error::InvalidMode`.
+    pub fn new(options: Options) -> Result<Self> {
+        let mut raw = core::ptr::null_mut();
+        let result = unsafe { lzsa_context_create(&options, &mut raw) };
+        if result != 0 {
+            return Err(Error::from(result));
+        }
+
+        Ok(Self {
+            raw: core::ptr::NonNull::new(raw).ok_or(Error::OutOfMemory)?,
+            options,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Get the options used for compression
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Compress `input` into a framed stream, returning it borrowed from the
+    /// internal buffer
+    pub fn compress(&mut self, input: &[u8]) -> Result<&[u8]> {
+        let max_size = max_compressed_size_for(input.len(), &self.options);
+        if self.buffer.len() < max_size {
+            try_resize(&mut self.buffer, max_size)?;
+        }
+
+        let size = traced!(
+            "compress",
+            self.options.version,
+            Mode::Normal,
+            input.len(),
+            {
+                let mut output_size = self.buffer.len();
+                let result = unsafe {
+                    lzsa_context_compress(
+                        self.raw.as_ptr(),
+                        input.as_ptr(),
+                        input.len(),
+                        self.buffer.as_mut_ptr(),
+                        &mut output_size,
+                    )
+                };
+                checked_compress(result, output_size, self.buffer.len())
+            }
+        )?;
+        Ok(&self.buffer[..size])
+    }
+
+    /// Decompress a framed stream (auto-detects LZSA1 or LZSA2), returning
+    /// the data borrowed from the internal buffer
+    pub fn decompress(&mut self, input: &[u8]) -> Result<&[u8]> {
+        if input.is_empty() {
+            return Ok(&[]);
+        }
+        check_framed_len(input)?;
+        check_truncated(input)?;
+        if empty_stream_version(input)?.is_some() {
+            return Ok(&[]);
+        }
+
+        let max_size = unsafe { lzsa_get_max_decompressed_size(input.as_ptr(), input.len()) };
+
+        if max_size == 0 {
+            return Err(Error::DecompressionFailed);
+        }
+
+        if self.buffer.len() < max_size {
+            try_resize(&mut self.buffer, max_size)?;
+        }
+
+        let size = traced!(
+            "decompress",
+            detect_version(input).ok(),
+            Mode::Normal,
+            input.len(),
+            {
+                let mut output_size = self.buffer.len();
+                let result = unsafe {
+                    lzsa_context_decompress(
+                        self.raw.as_ptr(),
+                        input.as_ptr(),
+                        input.len(),
+                        self.buffer.as_mut_ptr(),
+                        &mut output_size,
+                        core::ptr::null_mut(),
+                    )
+                };
+                checked_decompress(result, output_size, self.buffer.len())
+            }
+        )?;
+        Ok(&self.buffer[..size])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { lzsa_context_destroy(self.raw.as_ptr()) }
+    }
+}
+
+/// Shared set of [`Context`]s lent out to threads one call at a time
+///
+/// Setting up a C compressor costs time and memory, so a server would rather
+/// not create one per task, yet a `Context` serves one thread at a time.
+/// The pool keeps idle contexts, all with the same options, and hands one
+/// out per [`get`](Self::get) (a new one when none is idle); the guard puts
+/// it back when dropped. At most `max_idle` contexts are kept, so memory
+/// stays bounded by the number of threads that ever used the pool at once,
+/// capped at `max_idle` between bursts.
+///
+/// Nothing carries over between uses: every call compresses its input as a
+/// new stream with no history from the previous one, and only the bytes
+/// written by the current call are returned.
+///
+/// ```rust
+/// use lzsa_sys::{decompress, ContextPool, Options};
+///
+/// let pool = ContextPool::new(Options::default(), 4)?;
+/// std::thread::scope(|scope| {
+///     for worker in 0..4 {
+///         let pool = &pool;
+///         scope.spawn(move || {
+///             let message = format!("worker {worker}, worker {worker}").into_bytes();
+///             let compressed = pool.compress(&message).unwrap();
+///             assert_eq!(decompress(&compressed).unwrap(), message);
+///         });
+///     }
+/// });
+/// # Ok::<(), lzsa_sys::Error>(())
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ContextPool {
+    options: Options,
+    max_idle: usize,
+    idle: std::sync::Mutex<Vec<Context>>,
+}
+
+#[cfg(feature = "std")]
+impl ContextPool {
+    /// Create a pool of contexts compressing with `options`, keeping up to
+    /// `max_idle` of them between uses
+    ///
+    /// One context is created up front, so invalid options fail here with the
+    /// same errors as [`Context::new`].
+    pub fn new(options: Options, max_idle: usize) -> Result<Self> {
+        let first = Context::new(options)?;
+        let mut idle = Vec::with_capacity(max_idle);
+        if max_idle > 0 {
+            idle.push(first);
+        }
+
+        Ok(Self {
+            options,
+            max_idle,
+            idle: std::sync::Mutex::new(idle),
+        })
+    }
+
+    /// Get the options every context of the pool compresses with
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Borrow a context until the returned guard is dropped
+    pub fn get(&self) -> Result<PooledContext<'_>> {
+        let context = match self.lock_idle().pop() {
+            Some(context) => context,
+            None => Context::new(self.options)?,
+        };
+
+        Ok(PooledContext {
+            pool: self,
+            context: Some(context),
+        })
+    }
+
+    /// Compress `input` with a context from the pool
+    pub fn compress(&self, input: &[u8]) -> Result<Vec<u8>> {
+        self.get()?.compress(input).map(<[u8]>::to_vec)
+    }
+
+    /// Decompress `input` with a context from the pool
+    pub fn decompress(&self, input: &[u8]) -> Result<Vec<u8>> {
+        self.get()?.decompress(input).map(<[u8]>::to_vec)
+    }
+
+    /// Number of contexts currently waiting to be lent out
+    pub fn idle_count(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    fn lock_idle(&self) -> std::sync::MutexGuard<'_, Vec<Context>> {
+        // A panic while the lock was held cannot leave the list half-updated
+        self.idle
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// A [`Context`] borrowed from a [`ContextPool`], returned to it on drop
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct PooledContext<'a> {
+    pool: &'a ContextPool,
+    context: Option<Context>,
+}
+
+#[cfg(feature = "std")]
+impl core::ops::Deref for PooledContext<'_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.context
+            .as_ref()
+            .expect("context is only taken on drop")
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::ops::DerefMut for PooledContext<'_> {
+    fn deref_mut(&mut self) -> &mut Context {
+        self.context
+            .as_mut()
+            .expect("context is only taken on drop")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for PooledContext<'_> {
+    fn drop(&mut self) {
+        if let Some(context) = self.context.take() {
+            let mut idle = self.pool.lock_idle();
+            if idle.len() < self.pool.max_idle {
+                idle.push(context);
+            }
+        }
+    }
+}
+
+/// Decompress data into a caller-provided buffer (auto-detects LZSA1 or LZSA2)
+///
+/// Returns the number of bytes written to `output`, or
+/// `Error::BufferTooSmall` if the decompressed data does not fit. Unlike
+/// `decompress`, no size probe or allocation happens on success.
+///
+/// The C decompressor reports a full buffer like corrupt data, so a failure
+/// is classified afterwards. Stored blocks that alone overflow `output` are
+/// found from their frames without allocating; otherwise, with the `alloc`
+/// feature, the stream is decoded again one block at a time, which
+/// allocates two blocks (128 KiB). Without `alloc` the remaining cases are
+/// reported as `Error::DecompressionFailed`.
+pub fn decompress_into(input: &[u8], output: &mut [u8]) -> Result<usize> {
+    if input.is_empty() {
+        return Ok(0);
+    }
+
+    match decompress_inner(input, output) {
+        Ok((output_size, _)) => Ok(output_size),
+        Err(Error::DecompressionFailed) if stored_len(input) > output.len() => {
+            Err(Error::BufferTooSmall)
+        }
+        #[cfg(feature = "alloc")]
+        Err(Error::DecompressionFailed) if exceeds_output(input, output.len()) => {
+            Err(Error::BufferTooSmall)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Decompress data into `output`, reusing its allocation (auto-detects
+/// LZSA1 or LZSA2)
+///
+/// `output` is cleared, grown to the size the frame headers declare if it
+/// is smaller, and truncated to the decompressed data, so a `Vec` kept
+/// across calls stops allocating once it has held the largest output. On
+/// error it is left empty.
+#[cfg(feature = "alloc")]
+pub fn decompress_into_vec(input: &[u8], output: &mut Vec<u8>) -> Result<()> {
+    output.clear();
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    decompress_to(input, output).map(|_| ())
+}
+
+/// Decompress a framed stream stored at the end of `buffer` into its front
+///
+/// The compressed data must be the last `compressed_len` bytes of `buffer`,
+/// the layout loaders use when the file is read to the tail of its final
+/// destination. Space before it is reused for the output: the buffer only
+/// grows (keeping the compressed data at its tail) if the frame headers
+/// declare more output than the buffer holds. On success `buffer` is
+/// truncated to the decompressed data and its length is returned.
+///
+/// Output may overwrite compressed bytes that have not been read yet, so the
+/// C side decodes from a private copy of the `compressed_len` bytes; peak
+/// memory is the buffer plus that copy, not a second output buffer. Only
+/// framed streams (`Mode::Normal`) are supported. On error the contents of
+/// `buffer` are unspecified.
+#[cfg(feature = "alloc")]
+pub fn decompress_in_place(buffer: &mut Vec<u8>, compressed_len: usize) -> Result<usize> {
+    if compressed_len > buffer.len() {
+        return Err(Error::BufferTooSmall);
+    }
+
+    if compressed_len == 0 {
+        buffer.clear();
+        return Ok(0);
+    }
+
+    let start = buffer.len() - compressed_len;
+    let max_size = max_decompressed_size(&buffer[start..])?;
+    if max_size == 0 {
+        buffer.clear();
+        return Ok(0);
+    }
+
+    if buffer.len() < max_size {
+        let old_len = buffer.len();
+        try_resize(buffer, max_size)?;
+        buffer.copy_within(start..old_len, max_size - compressed_len);
+    }
+
+    let start = buffer.len() - compressed_len;
+    let output_size = traced!(
+        "decompress",
+        detect_version(&buffer[start..]).ok(),
+        Mode::Normal,
+        compressed_len,
+        {
+            let mut output_size = buffer.len();
+
+            // Input and output overlap, so both are passed as raw pointers into
+            // the same allocation; lzsa_decompress copies the input before writing
+            let result = unsafe {
+                let base = buffer.as_mut_ptr();
+                lzsa_decompress(
+                    base.add(start),
+                    compressed_len,
+                    base,
+                    &mut output_size,
+                    core::ptr::null_mut(),
+                )
+            };
+            checked_decompress(result, output_size, buffer.len())
+        }
+    )?;
+    buffer.truncate(output_size);
+    Ok(output_size)
+}
+
+/// Check the result and output size of a C compression call against the
+/// buffer it was given
+///
+/// See [`checked_ffi`]; a broken invariant is `Error::CompressionFailed`.
+pub(crate) fn checked_compress(
+    result: c_int,
+    output_size: usize,
+    capacity: usize,
+) -> Result<usize> {
+    checked_ffi(result, output_size, capacity, Error::CompressionFailed)
+}
+
+/// Check the result and output size of a C decompression call against the
+/// buffer it was given
+///
+/// See [`checked_ffi`]; a broken invariant is `Error::DecompressionFailed`.
+pub(crate) fn checked_decompress(
+    result: c_int,
+    output_size: usize,
+    capacity: usize,
+) -> Result<usize> {
+    checked_ffi(result, output_size, capacity, Error::DecompressionFailed)
+}
+
+/// Return the output size reported by the C side once it is known to fit
+///
+/// The wrapper returns 0 or one of the negative codes listed on [`Error`],
+/// and never reports more output than the buffer holds. Anything else means
+/// the two sides disagree about the interface, so it is returned as
+/// `failure` instead of being trusted: a positive result is not mistaken
+/// for an error code, and a bogus size never makes a slice reach past the
+/// buffer.
+fn checked_ffi(
+    result: c_int,
+    output_size: usize,
+    capacity: usize,
+    failure: Error,
+) -> Result<usize> {
+    if result < 0 {
+        return Err(Error::from(result));
+    }
+
+    if result > 0 || output_size > capacity {
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            result,
+            output_size,
+            capacity,
+            "C wrapper broke its interface"
+        );
+        return Err(failure);
+    }
+
+    Ok(output_size)
+}
+
+/// Shared FFI path of the auto-detecting decompressors
+///
+/// Returns the decompressed size and the version reported by the C side.
+fn decompress_inner(input: &[u8], output: &mut [u8]) -> Result<(usize, c_int)> {
+    check_framed_len(input)?;
+    check_truncated(input)?;
+
+    let mut detected_version: c_int = 0;
+    let output_size = traced!(
+        "decompress",
+        detect_version(input).ok(),
+        Mode::Normal,
+        input.len(),
+        unsafe {
+            let mut output_size = output.len();
+
+            let result = lzsa_decompress(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+                &mut detected_version,
+            );
+
+            checked_decompress(result, output_size, output.len())
+        }
+    )?;
+
+    Ok((output_size, detected_version))
+}
+
+/// Decompress LZSA1 data
+#[cfg(all(feature = "alloc", feature = "v1"))]
+pub fn decompress_v1(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    check_framed_len(input)?;
+    check_truncated(input)?;
+    if let Some(version) = empty_stream_version(input)? {
+        return match version {
+            Version::V1 => Ok(Vec::new()),
+            _ => Err(Error::InvalidFormat),
+        };
+    }
+
+    unsafe {
+        let max_size = lzsa_get_max_decompressed_size(input.as_ptr(), input.len());
+
+        if max_size == 0 {
+            return Err(Error::DecompressionFailed);
+        }
+
+        let mut output = try_zeroed(max_size)?;
+        let mut output_size = max_size;
+
+        let size = traced!("decompress", Version::V1, Mode::Normal, input.len(), {
+            let result = lzsa_decompress_v1(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+            );
+            checked_decompress(result, output_size, output.len())
+        })?;
+        output.truncate(size);
+        Ok(output)
+    }
+}
+
+/// Decompress LZSA2 data
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub fn decompress_v2(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    check_framed_len(input)?;
+    check_truncated(input)?;
+    if let Some(version) = empty_stream_version(input)? {
+        return match version {
+            Version::V2 => Ok(Vec::new()),
+            _ => Err(Error::InvalidFormat),
+        };
+    }
+
+    unsafe {
+        let max_size = lzsa_get_max_decompressed_size(input.as_ptr(), input.len());
+
+        if max_size == 0 {
+            return Err(Error::DecompressionFailed);
+        }
+
+        let mut output = try_zeroed(max_size)?;
+        let mut output_size = max_size;
+
+        let size = traced!("decompress", Version::V2, Mode::Normal, input.len(), {
+            let result = lzsa_decompress_v2(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+            );
+            checked_decompress(result, output_size, output.len())
+        })?;
+        output.truncate(size);
+        Ok(output)
+    }
+}
+
+/// Decompress a framed stream that must be in `version`
+///
+/// For batches whose version is known up front: the stream header is
+/// compared with [`Version::frame_magic`] and the version-specific
+/// decompressor is called directly. A stream in the other version returns
+/// `Error::InvalidVersion`, so mislabeled data is caught before decoding;
+/// input without a stream header returns `Error::InvalidFormat`, and a
+/// version left out of the build `Error::InvalidVersion`.
+#[cfg(feature = "alloc")]
+pub fn decompress_known(input: &[u8], version: Version) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if !input.starts_with(version.frame_magic()) {
+        return Err(match detect_version(input) {
+            Ok(_) => Error::InvalidVersion,
+            Err(error) => error,
+        });
+    }
+
+    match version {
+        #[cfg(feature = "v1")]
+        Version::V1 => decompress_v1(input),
+        #[cfg(feature = "v2")]
+        Version::V2 => decompress_v2(input),
+        #[cfg(not(all(feature = "v1", feature = "v2")))]
+        _ => Err(Error::InvalidVersion),
+    }
+}
+
+/// Decompress an LZSA1 raw block produced with `Mode::RawBackward`
+#[cfg(all(feature = "alloc", feature = "v1"))]
+pub fn decompress_v1_backward(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    unsafe {
+        // Raw blocks carry no size, but never decode to more than one block
+        let mut output = vec![0u8; BLOCK_SIZE];
+        let mut output_size = BLOCK_SIZE;
+
+        let size = traced!("decompress", Version::V1, Mode::RawBackward, input.len(), {
+            let result = lzsa_decompress_v1_backward(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+            );
+            checked_decompress(result, output_size, output.len())
+        })?;
+        output.truncate(size);
+        Ok(output)
+    }
+}
+
+/// Decompress an LZSA2 raw block produced with `Mode::RawBackward`
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub fn decompress_v2_backward(input: &[u8]) -> Result<Vec<u8>> {
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    unsafe {
+        // Raw blocks carry no size, but never decode to more than one block
+        let mut output = vec![0u8; BLOCK_SIZE];
+        let mut output_size = BLOCK_SIZE;
+
+        let size = traced!("decompress", Version::V2, Mode::RawBackward, input.len(), {
+            let result = lzsa_decompress_v2_backward(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+            );
+            checked_decompress(result, output_size, output.len())
+        })?;
+        output.truncate(size);
+        Ok(output)
+    }
+}
+
+/// Marker that starts the trailer appended by [`compress_checked`]
+#[cfg(feature = "alloc")]
+const CHECKSUM_MAGIC: [u8; 4] = *b"LZC4";
+
+/// Compress data into a framed stream followed by a CRC-32 of `input`
+///
+/// The stream is followed by an 8-byte trailer: the marker `LZC4` and the
+/// little-endian CRC-32 (IEEE) of the uncompressed data. Use
+/// [`decompress_checked`] to verify it; plain [`decompress`] stops at the
+/// stream footer and ignores the trailer. Only `Mode::Normal` has a footer
+/// to append to, so raw modes return `Error::InvalidMode`.
+#[cfg(feature = "alloc")]
+pub fn compress_checked(input: &[u8], options: &Options) -> Result<Vec<u8>> {
+    if options.mode != Mode::Normal {
+        return Err(Error::InvalidMode);
+    }
+
+    if input.is_empty() {
+        // An empty payload still needs a stream for the trailer to follow
+        let mut output = vec![0u8; HEADER_SIZE + FRAME_SIZE];
+        let mut header_size = HEADER_SIZE;
+        let mut footer_size = FRAME_SIZE;
+        unsafe {
+            let result = lzsa_encode_stream_header(
+                output.as_mut_ptr(),
+                &mut header_size,
+                options.version as c_int,
+            );
+            let header_size = checked_compress(result, header_size, HEADER_SIZE)?;
+            let result =
+                lzsa_encode_stream_footer(output[header_size..].as_mut_ptr(), &mut footer_size);
+            let footer_size = checked_compress(result, footer_size, FRAME_SIZE)?;
+            output.truncate(header_size + footer_size);
+        }
+        output.extend_from_slice(&CHECKSUM_MAGIC);
+        output.extend_from_slice(&crc32::crc32(input).to_le_bytes());
+        return Ok(output);
+    }
+
+    let mut output = compress_with_options(input, options)?;
+    output.extend_from_slice(&CHECKSUM_MAGIC);
+    output.extend_from_slice(&crc32::crc32(input).to_le_bytes());
+    Ok(output)
+}
+
+/// Decompress data made by [`compress_checked`] and verify its checksum
+///
+/// Returns `Error::InvalidFormat` if the checksum trailer is missing or
+/// anything follows it, and `Error::ChecksumMismatch` if the decompressed
+/// data does not match the stored CRC-32.
+#[cfg(feature = "alloc")]
+pub fn decompress_checked(input: &[u8]) -> Result<Vec<u8>> {
+    let stream_len = framed_stream_len(input)?;
+    let trailer = &input[stream_len..];
+
+    if trailer.len() != CHECKSUM_MAGIC.len() + 4 || trailer[..4] != CHECKSUM_MAGIC {
+        return Err(Error::InvalidFormat);
+    }
+    let expected = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+
+    let output = decompress(&input[..stream_len])?;
+    if crc32::crc32(&output) != expected {
+        return Err(Error::ChecksumMismatch);
+    }
+
+    Ok(output)
+}
+
+/// Compress `input` into a single raw block, without any framing
+///
+/// This is `Mode::RawForward` with default quality and minimum match size,
+/// for decompressors that expect a bare block (e.g. assets in a ROM). Raw
+/// blocks hold at most 64 KiB; larger inputs return
+/// `Error::InvalidBlockSize`.
+#[cfg(feature = "alloc")]
+pub fn compress_raw(input: &[u8], version: Version) -> Result<Vec<u8>> {
+    let options = Options {
+        version,
+        mode: Mode::RawForward,
+        ..Options::default()
+    };
+    compress_with_options(input, &options)
+}
+
+/// Decompress a raw block made by [`compress_raw`] or `Mode::RawForward`
+///
+/// Raw blocks record neither their version nor their size, so both must be
+/// supplied. Data that does not decompress to exactly `expected_len` bytes
+/// returns `Error::DecompressionFailed`.
+#[cfg(feature = "alloc")]
+pub fn decompress_raw(input: &[u8], version: Version, expected_len: usize) -> Result<Vec<u8>> {
+    if input.is_empty() && expected_len == 0 {
+        return Ok(Vec::new());
+    }
+
+    unsafe {
+        let mut output = try_zeroed(expected_len)?;
+        let mut output_size = expected_len;
+
+        let size = traced!("decompress", version, Mode::RawForward, input.len(), {
+            let result = lzsa_decompress_raw(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                &mut output_size,
+                version as c_int,
+            );
+            checked_decompress(result, output_size, output.len())
+        })?;
+        if size != expected_len {
+            return Err(Error::DecompressionFailed);
+        }
+
+        Ok(output)
+    }
+}
+
+/// Options for [`compress_block_v2`] and [`decompress_block_v2_with`]
+///
+/// `history` is data that precedes the block in decoding order, which
+/// matches may refer back to: the previous block of a framed stream, or a
+/// dictionary. Only the 64 KiB nearest the block are used, i.e. the end of
+/// `history` going forward and its start going backward. The decompressor
+/// must be given the same history as the compressor.
+///
+/// `backward` produces and reads blocks for decompressors that run from the
+/// end of the data towards the start, as `Mode::RawBackward` does. History
+/// then follows the block in memory.
+#[cfg(all(feature = "alloc", feature = "v2"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockOptions<'a> {
+    pub quality: Quality,
+    pub min_match_size: u32,
+    pub effort: u32,
+    pub backward: bool,
+    pub history: &'a [u8],
+}
+
+#[cfg(all(feature = "alloc", feature = "v2"))]
+impl Default for BlockOptions<'_> {
+    fn default() -> Self {
+        Self {
+            quality: Quality::Ratio,
+            min_match_size: MIN_MATCH_V2,
+            effort: Options::EFFORT_FROM_QUALITY,
+            backward: false,
+            history: &[],
+        }
+    }
+}
+
+#[cfg(all(feature = "alloc", feature = "v2"))]
+impl BlockOptions<'_> {
+    /// The history and block in the order the forward codec sees them
+    fn window(&self, block: &[u8]) -> Vec<u8> {
+        let mut window = Vec::with_capacity(BLOCK_SIZE + block.len());
+        if self.backward {
+            let history = &self.history[..self.history.len().min(BLOCK_SIZE)];
+            window.extend(history.iter().rev());
+            window.extend(block.iter().rev());
+        } else {
+            let history = &self.history[self.history.len().saturating_sub(BLOCK_SIZE)..];
+            window.extend_from_slice(history);
+            window.extend_from_slice(block);
+        }
+        window
+    }
+}
+
+/// Compress `input` into one LZSA2 block, without any framing
+///
+/// The output is exactly the data that follows a compressed block frame in
+/// a framed stream, and the frame-level functions build their streams from
+/// such blocks: [`compress_with_options`] adds the header, a frame per
+/// block and the footer, and stores blocks that do not shrink. Unlike a
+/// raw block from [`compress_raw`], there is no end-of-data marker, so the
+/// decompressed size must be known, and [`BlockOptions::history`] carries
+/// the previous block or a dictionary across calls.
+///
+/// Blocks hold at most 64 KiB; larger inputs return
+/// `Error::InvalidBlockSize`. Data that does not compress to fit the
+/// worst-case bound returns `Error::CompressionFailed`, where the framed
+/// API would store it instead.
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub fn compress_block_v2(input: &[u8], options: &BlockOptions<'_>) -> Result<Vec<u8>> {
+    if input.len() > BLOCK_SIZE {
+        return Err(Error::InvalidBlockSize);
+    }
+
+    let window = options.window(input);
+    let block_options = Options {
+        version: Version::V2,
+        quality: options.quality,
+        min_match_size: options.min_match_size,
+        effort: options.effort,
+        ..Options::default()
+    };
+
+    unsafe {
+        let max_size = max_compressed_size(input.len(), Version::V2, Mode::RawForward);
+        let mut output = try_zeroed(max_size)?;
+        let mut output_size = max_size;
+
+        let size = traced!(
+            "compress_block",
+            Version::V2,
+            if options.backward {
+                Mode::RawBackward
+            } else {
+                Mode::RawForward
+            },
+            input.len(),
+            {
+                let result = lzsa_compress_block_data(
+                    window.as_ptr(),
+                    window.len() - input.len(),
+                    input.len(),
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    &block_options,
+                );
+                checked_compress(result, output_size, output.len())
+            }
+        )?;
+        output.truncate(size);
+        if options.backward {
+            output.reverse();
+        }
+        Ok(output)
+    }
+}
+
+/// Decompress an LZSA2 block made by [`compress_block_v2`] with default
+/// options
+///
+/// Blocks record neither their size nor where they end, so `expected_len`
+/// must be supplied. Data that does not decompress to exactly
+/// `expected_len` bytes returns `Error::DecompressionFailed`.
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub fn decompress_block_v2(input: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    decompress_block_v2_with(input, expected_len, &BlockOptions::default())
+}
+
+/// Decompress an LZSA2 block made by [`compress_block_v2`]
+///
+/// `options` must have the `backward` and `history` the block was
+/// compressed with; the other fields are ignored. Otherwise this is
+/// [`decompress_block_v2`].
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub fn decompress_block_v2_with(
+    input: &[u8],
+    expected_len: usize,
+    options: &BlockOptions<'_>,
+) -> Result<Vec<u8>> {
+    if expected_len > BLOCK_SIZE || input.len() > BLOCK_SIZE {
+        return Err(Error::InvalidBlockSize);
+    }
+
+    let mut block = input.to_vec();
+    if options.backward {
+        block.reverse();
+    }
+
+    // The block is decoded into the window, right after the history
+    let mut window = options.window(&[]);
+    let previous_len = window.len();
+    window.resize(previous_len + expected_len, 0);
+    let size = traced!(
+        "decompress_block",
+        Version::V2,
+        if options.backward {
+            Mode::RawBackward
+        } else {
+            Mode::RawForward
+        },
+        block.len(),
+        {
+            let mut output_size = expected_len;
+            let result = unsafe {
+                lzsa_decompress_block(
+                    block.as_ptr(),
+                    block.len(),
+                    window.as_mut_ptr(),
+                    previous_len,
+                    &mut output_size,
+                    Version::V2 as c_int,
+                )
+            };
+            checked_decompress(result, output_size, expected_len)
+        }
+    )?;
+    if size != expected_len {
+        return Err(Error::DecompressionFailed);
+    }
+
+    window.drain(..previous_len);
+    if options.backward {
+        window.reverse();
+    }
+    Ok(window)
+}
+
+/// Decompress one block into `output`, writing from `offset` onwards
+///
+/// `input` is block data as it appears after a compressed block frame in a
+/// framed stream, such as the output of [`compress_block_v2`]. Nothing
+/// marks where a block ends, so it is decoded until `input` runs out, and
+/// the number of bytes written at `offset` is returned.
+///
+/// `output` is the decoding window. Matches may copy from up to 64 KiB
+/// before `offset`, so `output[..offset]` must already hold the data that
+/// precedes the block, exactly as the compressor saw it: the previous block
+/// of the stream or a dictionary. Bytes further back are never read, and
+/// the history is not modified. The block must fit in `output[offset..]`
+/// and decodes to at most 64 KiB; the decoder may use the space after the
+/// decoded bytes, within that limit, for fast copies, so its contents are
+/// not preserved. `input` cannot overlap `output`; it is copied before the
+/// C code runs in any case.
+///
+/// Returns `Error::OutOfRange` if `offset` is past the end of `output`,
+/// `Error::InvalidBlockSize` if `input` is larger than 64 KiB, and
+/// `Error::InvalidFormat` if the block does not fit in the space left.
+pub fn decompress_block_at(
+    input: &[u8],
+    output: &mut [u8],
+    offset: usize,
+    version: Version,
+) -> Result<usize> {
+    if offset > output.len() {
+        return Err(Error::OutOfRange);
+    }
+
+    let previous_len = offset.min(BLOCK_SIZE);
+    let window = &mut output[offset - previous_len..];
+    let capacity = (window.len() - previous_len).min(BLOCK_SIZE);
+
+    traced!("decompress_block", version, Mode::Normal, input.len(), {
+        let mut output_size = capacity;
+        let result = unsafe {
+            lzsa_decompress_block(
+                input.as_ptr(),
+                input.len(),
+                window.as_mut_ptr(),
+                previous_len,
+                &mut output_size,
+                version as c_int,
+            )
+        };
+        checked_decompress(result, output_size, capacity)
+    })
+}
+
+/// Check that compressing `input` with `options` is lossless
+///
+/// Compresses, decompresses with the matching decompressor and compares.
+/// Returns `Ok(false)` if the data differs and propagates any error from
+/// either step.
+#[cfg(feature = "alloc")]
+pub fn verify_roundtrip(input: &[u8], options: &Options) -> Result<bool> {
+    let compressed = compress_with_options(input, options)?;
+    let decompressed = decompress_matching(&compressed, input.len(), options)?;
+
+    Ok(decompressed == input)
+}
+
+/// Decompress the output of [`compress_with_options`] for an input of
+/// `input_len` bytes, picking the decompressor from `options`
+#[cfg(feature = "alloc")]
+pub(crate) fn decompress_matching(
+    compressed: &[u8],
+    input_len: usize,
+    options: &Options,
+) -> Result<Vec<u8>> {
+    match (options.mode, options.version) {
+        (Mode::Normal, _) => decompress(compressed),
+        (Mode::RawForward, version) => decompress_raw(compressed, version, input_len),
+        #[cfg(feature = "v1")]
+        (Mode::RawBackward, Version::V1) => decompress_v1_backward(compressed),
+        #[cfg(feature = "v2")]
+        (Mode::RawBackward, Version::V2) => decompress_v2_backward(compressed),
+        #[cfg(not(all(feature = "v1", feature = "v2")))]
+        (Mode::RawBackward, _) => Err(Error::InvalidVersion),
+    }
+}
+
+// `#[test]` functions do not run under wasm-bindgen-test-runner
+#[cfg(all(test, target_arch = "wasm32", feature = "v1"))]
+mod wasm_tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_round_trip() {
+        let original = b"Hello from WebAssembly, hello from WebAssembly!";
+        let compressed = compress_v1(original).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), original);
+    }
+}
+
+#[cfg(all(test, feature = "std", feature = "v1", feature = "v2"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_decompress_v1() {
+        let original = b"Hello, world! This is a test.";
+        let compressed = compress_v1(original).unwrap();
+        let decompressed = decompress(&compressed).unwrap();
+        assert_eq!(original, decompressed.as_slice());
+    }
+
+    #[test]
+    fn test_compress_decompress_v2() {
+        let original = b"Hello, world! This is a test.";
+        let compressed = compress_v2(original).unwrap();
+        let decompressed = decompress(&compressed).unwrap();
+        assert_eq!(original, decompressed.as_slice());
+    }
+
+    #[test]
+    fn test_custom_options() {
+        let original = b"Custom options test data";
+        let options = Options {
+            version: Version::V2,
+            mode: Mode::Normal,
+            quality: Quality::Speed,
+            min_match_size: 3,
+            effort: Options::EFFORT_FROM_QUALITY,
+            block_size: BLOCK_SIZE as u32,
+        };
+        let compressed = compress_with_options(original, &options).unwrap();
+        let decompressed = decompress(&compressed).unwrap();
+        assert_eq!(original, decompressed.as_slice());
+    }
+
+    #[test]
+    fn test_c_default_options() {
+        assert_eq!(c_default_options(), Options::default());
+    }
+
+    #[test]
+    fn test_effort() {
+        let original = b"Effort levels, effort levels, effort levels, effort levels.".repeat(40);
+        let compress = |quality, effort| {
+            let options = Options {
+                quality,
+                effort,
+                ..Options::default()
+            };
+            compress_with_options(&original, &options).unwrap()
+        };
+
+        let speed = compress(Quality::Speed, Options::EFFORT_FROM_QUALITY);
+        let ratio = compress(Quality::Ratio, Options::EFFORT_FROM_QUALITY);
+        assert_eq!(compress(Quality::Ratio, Quality::Speed.effort()), speed);
+        assert_eq!(compress(Quality::Speed, Quality::Ratio.effort()), ratio);
+
+        for effort in 0..=9 {
+            let compressed = compress(Quality::Ratio, effort);
+            assert_eq!(decompress(&compressed).unwrap(), original);
+            assert_eq!(&compressed, if effort < 5 { &speed } else { &ratio });
+        }
+        assert_eq!(compress(Quality::Speed, 100), ratio);
+    }
+
+    #[test]
+    fn test_block_size_option() {
+        let mut state = 0x1234_5678_u32;
+        let noise: Vec<u8> = (0..10_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let options = Options {
+            block_size: 1024,
+            ..Options::default()
+        };
+
+        let (compressed, stats) = compress_with_stats(&noise, &options).unwrap();
+        assert_eq!(stats.block_count, 10);
+        assert_eq!(compress_with_options(&noise, &options).unwrap(), compressed);
+        assert_eq!(decompress(&compressed).unwrap(), noise);
+
+        let mut pos = HEADER_SIZE;
+        loop {
+            let (block_size, _) = decode_block_frame(&compressed[pos..]).unwrap();
+            assert!(block_size <= 1024);
+            pos += FRAME_SIZE + block_size;
+            if block_size == 0 {
+                break;
+            }
+        }
+        assert_eq!(pos, compressed.len());
+
+        // Incompressible data needs the extra framing in the worst case
+        let mut output = vec![0u8; max_compressed_size_for(noise.len(), &options)];
+        assert!(output.len() > max_compressed_size(noise.len(), options.version, options.mode));
+        assert!(compress_into(&noise, &mut output, &options).is_ok());
+
+        let tiny = Options {
+            block_size: 1,
+            ..options
+        };
+        let compressed = compress_with_options(b"one byte blocks", &tiny).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), b"one byte blocks");
+
+        for block_size in [0, BLOCK_SIZE as u32 + 1] {
+            let bad = Options {
+                block_size,
+                ..options
+            };
+            assert_eq!(
+                compress_with_options(b"data", &bad),
+                Err(Error::InvalidBlockSize)
+            );
+            assert_eq!(
+                compress_with_stats(b"data", &bad).err(),
+                Some(Error::InvalidBlockSize)
+            );
+        }
+    }
+
+    #[test]
+    fn test_expanding_blocks_are_stored() {
+        let mut noise = noise_block();
+        noise.push(0x41);
+        let small_blocks = Options::builder().block_size(4096).build();
+        let pool = ContextPool::new(Options::default(), 1).unwrap();
+
+        // Each block stays stored at its input size, and the footer fits
+        let stored_len = |input: &[u8], block_size: usize| {
+            HEADER_SIZE + input.len() + input.len().div_ceil(block_size) * FRAME_SIZE + FRAME_SIZE
+        };
+        for input in [b"A".as_slice(), &noise[..4097], &noise] {
+            let entry_points: [(&str, Result<Vec<u8>>, usize); 6] = [
+                (
+                    "compress_with_options",
+                    compress_with_options(input, &Options::default()),
+                    BLOCK_SIZE,
+                ),
+                (
+                    "compress_with_options at 4096",
+                    compress_with_options(input, &small_blocks),
+                    4096,
+                ),
+                (
+                    "compress_with_dictionary",
+                    compress_with_dictionary(input, b"dictionary", &Options::default()),
+                    BLOCK_SIZE,
+                ),
+                (
+                    "compress_with_stats",
+                    compress_with_stats(input, &Options::default()).map(|(data, _)| data),
+                    BLOCK_SIZE,
+                ),
+                (
+                    "Context::compress",
+                    Context::new(Options::default())
+                        .and_then(|mut context| context.compress(input).map(<[u8]>::to_vec)),
+                    BLOCK_SIZE,
+                ),
+                ("ContextPool::compress", pool.compress(input), BLOCK_SIZE),
+            ];
+            for (name, compressed, block_size) in entry_points {
+                let compressed = compressed.unwrap_or_else(|error| {
+                    panic!("{name} failed on {} bytes: {error:?}", input.len())
+                });
+                assert_eq!(compressed.len(), stored_len(input, block_size), "{name}");
+                assert_eq!(decompress(&compressed).unwrap(), input, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_options_builder() {
+        assert_eq!(OptionsBuilder::new().build(), Options::default());
+
+        let options = OptionsBuilder::new()
+            .version(Version::V2)
+            .mode(Mode::RawForward)
+            .quality(Quality::Speed)
+            .min_match_size(4)
+            .effort(2)
+            .block_size(1024)
+            .build();
+        assert_eq!(
+            options,
+            Options {
+                version: Version::V2,
+                mode: Mode::RawForward,
+                quality: Quality::Speed,
+                min_match_size: 4,
+                effort: 2,
+                block_size: 1024,
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_min_match() {
+        let original = b"Minimum match size test data";
+        for (version, min_match_size) in [
+            (Version::V1, 0),
+            (Version::V1, 2),
+            (Version::V2, 0),
+            (Version::V1, 100),
+            (Version::V2, 100),
+        ] {
+            let options = Options {
+                version,
+                min_match_size,
+                ..Options::default()
+            };
+            assert_eq!(
+                compress_with_options(original, &options),
+                Err(Error::InvalidMinMatch),
+                "{version:?} min_match_size {min_match_size}"
+            );
+        }
+
+        // LZSA2 accepts shorter matches than LZSA1
+        let options = Options {
+            version: Version::V2,
+            min_match_size: 2,
+            ..Options::default()
+        };
+        let compressed = compress_with_options(original, &options).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_options_clamped() {
+        let original = b"Clamped options, clamped options, clamped options";
+        for (version, min) in [(Version::V1, MIN_MATCH_V1), (Version::V2, MIN_MATCH_V2)] {
+            for (min_match_size, clamped) in [
+                (0, min),
+                (min - 1, min),
+                (min, min),
+                (MAX_MIN_MATCH, MAX_MIN_MATCH),
+                (MAX_MIN_MATCH + 1, MAX_MIN_MATCH),
+                (u32::MAX, MAX_MIN_MATCH),
+            ] {
+                let options = Options {
+                    version,
+                    min_match_size,
+                    ..Options::default()
+                }
+                .clamped();
+                assert_eq!(options.min_match_size, clamped);
+                let compressed = compress_with_options(original, &options).unwrap();
+                assert_eq!(decompress(&compressed).unwrap(), original);
+            }
+        }
+
+        let options = Options {
+            effort: 10,
+            block_size: 0,
+            ..Options::default()
+        };
+        let clamped = options.clamped();
+        assert_eq!((clamped.effort, clamped.block_size), (9, 1));
+        assert_eq!(
+            compress_with_options(original, &options),
+            Err(Error::InvalidBlockSize)
+        );
+
+        let options = Options {
+            block_size: BLOCK_SIZE as u32 + 1,
+            ..Options::default()
+        };
+        assert_eq!(options.clamped().block_size, BLOCK_SIZE as u32);
+
+        // Valid options, the defaults included, come back unchanged
+        assert_eq!(Options::default().clamped(), Options::default());
+    }
+
+    #[test]
+    fn test_size_constants() {
+        let original = b"Size constant test data, size constant test data";
+        for (version, min) in [(Version::V1, MIN_MATCH_V1), (Version::V2, MIN_MATCH_V2)] {
+            assert_eq!(version.min_match_sizes(), min..=MAX_MIN_MATCH);
+
+            for (min_match_size, valid) in [
+                (min - 1, false),
+                (min, true),
+                (MAX_MIN_MATCH, true),
+                (MAX_MIN_MATCH + 1, false),
+            ] {
+                let options = Options {
+                    version,
+                    min_match_size,
+                    ..Options::default()
+                };
+                let result = compress_with_options(original, &options);
+                assert_eq!(
+                    result.is_ok(),
+                    valid,
+                    "{version:?} min_match_size {min_match_size}"
+                );
+                if let Ok(compressed) = result {
+                    assert_eq!(compressed[..2], FRAME_MAGIC);
+                }
+            }
+        }
+
+        let options = Options {
+            block_size: MAX_BLOCK_SIZE as u32 + 1,
+            ..Options::default()
+        };
+        assert_eq!(
+            compress_with_options(original, &options),
+            Err(Error::InvalidBlockSize)
+        );
+    }
+
+    #[test]
+    fn test_compress_fixed() {
+        let input = b"Fixed capacity, fixed capacity, fixed capacity.";
+        let options = Options::default();
+
+        let (buffer, len) = compress_fixed::<{ 47 + 9 }>(input, &options).unwrap();
+        assert_eq!(decompress(&buffer[..len]).unwrap(), input);
+        assert_eq!(
+            &buffer[..len],
+            compress_with_options(input, &options).unwrap()
+        );
+
+        let (_, len) = compress_fixed::<0>(&[], &options).unwrap();
+        assert_eq!(len, 0);
+
+        assert_eq!(
+            compress_fixed::<8>(input, &options).err(),
+            Some(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_compressor_reuse() {
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+        let mut compressor = Compressor::new(options);
+
+        let large = b"Reusable buffers, reusable buffers. ".repeat(100);
+        let compressed = compressor.compress(&large).unwrap().to_vec();
+        assert_eq!(compressed, compress_with_options(&large, &options).unwrap());
+        let capacity = compressor.buffer.len();
+
+        // Smaller inputs reuse the buffer without growing it
+        let small = b"Small input, small input.";
+        let compressed = compressor.compress(small).unwrap();
+        assert_eq!(decompress(compressed).unwrap(), small);
+        assert_eq!(compressor.buffer.len(), capacity);
+
+        assert!(compressor.compress(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decompressor_reuse() {
+        let large = vec![b'z'; BLOCK_SIZE + 1];
+        let small = b"Small chunk, small chunk.";
+        let chunks = [compress_v2(&large).unwrap(), compress_v1(small).unwrap()];
+
+        let mut decompressor = Decompressor::new();
+        assert_eq!(decompressor.version(), None);
+
+        assert_eq!(decompressor.decompress(&chunks[0]).unwrap(), large);
+        assert_eq!(decompressor.version(), Some(Version::V2));
+        let capacity = decompressor.buffer.len();
+
+        assert_eq!(decompressor.decompress(&chunks[1]).unwrap(), small);
+        assert_eq!(decompressor.version(), Some(Version::V1));
+        assert_eq!(decompressor.buffer.len(), capacity);
+
+        assert_eq!(
+            decompressor.decompress(b"garbage"),
+            Err(Error::DecompressionFailed)
+        );
+        assert_eq!(decompressor.version(), Some(Version::V1));
+    }
+
+    #[test]
+    fn test_context_reuse() {
+        for version in [Version::V1, Version::V2] {
+            let options = Options {
+                version,
+                block_size: 1024,
+                ..Options::default()
+            };
+            let mut context = Context::new(options).unwrap();
+            assert_eq!(context.options(), &options);
+
+            let large = b"Context reuse, context reuse. ".repeat(200);
+            for message in [&large[..], b"tiny", b"", b"Second small message, small."] {
+                let compressed = context.compress(message).unwrap().to_vec();
+                assert_eq!(decompress(&compressed).unwrap(), message);
+                assert_eq!(context.decompress(&compressed).unwrap(), message);
+            }
+
+            // Output from the free functions decompresses too
+            let compressed = compress_with_options(&large, &Options::default()).unwrap();
+            assert_eq!(context.decompress(&compressed).unwrap(), large);
+            assert_eq!(
+                context.decompress(b"garbage"),
+                Err(Error::DecompressionFailed)
+            );
+        }
+
+        // Each context frees its handle once on drop
+        for _ in 0..100 {
+            drop(Context::new(Options::default()).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_context_invalid_options() {
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(Context::new(raw).err(), Some(Error::InvalidMode));
+
+        let bad_min_match = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(
+            Context::new(bad_min_match).err(),
+            Some(Error::InvalidMinMatch)
+        );
+
+        let bad_block_size = Options {
+            block_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(
+            Context::new(bad_block_size).err(),
+            Some(Error::InvalidBlockSize)
+        );
+    }
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let input = b"Lossless, lossless, lossless, lossless.".repeat(20);
+        for version in [Version::V1, Version::V2] {
+            for mode in [Mode::Normal, Mode::RawForward, Mode::RawBackward] {
+                let options = Options {
+                    version,
+                    mode,
+                    ..Options::default()
+                };
+                assert_eq!(verify_roundtrip(&input, &options), Ok(true));
+            }
+        }
+        assert_eq!(verify_roundtrip(&[], &Options::default()), Ok(true));
+
+        let bad = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(verify_roundtrip(&input, &bad), Err(Error::InvalidMinMatch));
+    }
+
+    #[test]
+    fn test_checked_round_trip() {
+        let original = b"Flaky media, flaky media, flaky media.".repeat(30);
+        let options = Options::default();
+
+        let checked = compress_checked(&original, &options).unwrap();
+        assert_eq!(decompress_checked(&checked).unwrap(), original);
+        // The trailer does not get in the way of plain decompression
+        assert_eq!(decompress(&checked).unwrap(), original);
+
+        let empty = compress_checked(&[], &options).unwrap();
+        assert_eq!(decompress_checked(&empty).unwrap(), Vec::<u8>::new());
+
+        // A flipped bit in the stored CRC
+        let mut corrupt = checked.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert_eq!(decompress_checked(&corrupt), Err(Error::ChecksumMismatch));
+
+        let unchecked = compress_v1(&original).unwrap();
+        assert_eq!(decompress_checked(&unchecked), Err(Error::InvalidFormat));
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(compress_checked(&original, &raw), Err(Error::InvalidMode));
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        let original = b"ROM asset, ROM asset, ROM asset, ROM asset.".repeat(10);
+        for version in [Version::V1, Version::V2] {
+            let raw = compress_raw(&original, version).unwrap();
+            let framed = match version {
+                Version::V1 => compress_v1(&original).unwrap(),
+                Version::V2 => compress_v2(&original).unwrap(),
+            };
+            assert!(raw.len() < framed.len());
+            assert_eq!(
+                decompress_raw(&raw, version, original.len()).unwrap(),
+                original
+            );
+            assert_eq!(
+                decompress_raw(&raw, version, original.len() + 1),
+                Err(Error::DecompressionFailed)
+            );
+        }
+        assert_eq!(
+            decompress_raw(&[], Version::V1, 0).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn test_compress_to_writer() {
+        let original = b"Straight into the sink, straight into the sink.".repeat(10);
+        let options = Options::default();
+
+        let mut sink = Vec::new();
+        let written = compress_to_writer(&original, &mut sink, &options).unwrap();
+        assert_eq!(written, sink.len());
+        assert_eq!(decompress(&sink).unwrap(), original);
+
+        let mut full = [0u8; 4];
+        let mut cursor = std::io::Cursor::new(&mut full[..]);
+        assert_eq!(
+            compress_to_writer(&original, &mut cursor, &options),
+            Err(Error::Io(std::io::ErrorKind::WriteZero))
+        );
+        let io_error: std::io::Error = Error::Io(std::io::ErrorKind::WriteZero).into();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::WriteZero);
+        assert_eq!(
+            Error::from(std::io::Error::other("disk on fire")),
+            Error::Io(std::io::ErrorKind::Other)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let original = b"Off the reactor, off the reactor, off the reactor.".repeat(20);
+
+        let compressed = runtime
+            .block_on(compress_async(original.clone(), Options::default()))
+            .unwrap();
+        assert_eq!(
+            compressed,
+            compress_with_options(&original, &Options::default()).unwrap()
+        );
+        assert_eq!(
+            runtime.block_on(decompress_async(compressed)).unwrap(),
+            original
+        );
+
+        let invalid = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(
+            runtime.block_on(compress_async(original, invalid)),
+            Err(Error::InvalidMinMatch)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_compress_parallel() {
+        let original: Vec<u8> = (0..300_000u32)
+            .map(|i| (i % 251) as u8 ^ (i / 4096) as u8)
+            .collect();
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+
+        let compressed = compress_parallel(&original, &options, 100_000).unwrap();
+        assert_eq!(decompress_all(&compressed).unwrap(), original);
+        assert!(
+            compress_parallel(&[], &options, 100_000)
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            compress_parallel(&original, &options, 0),
+            Err(Error::InvalidBlockSize)
+        );
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(
+            compress_parallel(&original, &raw, 100_000),
+            Err(Error::InvalidMode)
+        );
+    }
+
+    #[test]
+    fn test_compress_batch() {
+        let text = b"Batch item, batch item, batch item.".repeat(50);
+        let oversized = vec![1u8; BLOCK_SIZE + 1];
+        let inputs: [&[u8]; 4] = [&text, b"", &oversized, b"last"];
+
+        let options = Options::default();
+        let results = compress_batch(&inputs, &options);
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.iter().zip(&results) {
+            assert_eq!(result, &compress_with_options(input, &options));
+            assert_eq!(decompress(result.as_ref().unwrap()).unwrap(), *input);
+        }
+
+        // The input too large for a raw block fails alone
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        let results = compress_batch(&inputs, &raw);
+        assert_eq!(results[2], Err(Error::InvalidBlockSize));
+        assert!(results[0].is_ok() && results[3].is_ok());
+        assert!(compress_batch(&[], &options).is_empty());
+    }
+
+    #[test]
+    fn test_estimate_ratio() {
+        let options = Options::default();
+
+        let text = b"Repetitive payload, repetitive payload. ".repeat(10_000);
+        let ratio = estimate_ratio(&text, &options).unwrap();
+        assert!(ratio > 0.0 && ratio < 0.2, "ratio {ratio}");
+
+        let mut state = 0x2545_f491_u32;
+        let noise: Vec<u8> = (0..200_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let ratio = estimate_ratio(&noise, &options).unwrap();
+        assert!(ratio > 0.9 && ratio <= 1.0, "ratio {ratio}");
+
+        // The exact ratio covers the whole input and is not capped
+        let exact = super::ratio(&noise, &options).unwrap();
+        let size = compressed_size(&noise, &options).unwrap();
+        assert_eq!(exact, size as f64 / noise.len() as f64);
+        assert!(exact > 1.0, "ratio {exact}");
+        assert_eq!(super::ratio(&[], &options), Ok(1.0));
+
+        assert_eq!(estimate_ratio(&[], &options), Ok(1.0));
+    }
+
+    #[test]
+    fn test_compress_or_store() {
+        use alloc::borrow::Cow;
+
+        let options = Options::default();
+
+        let text = b"Compressible, compressible, compressible, compressible.".repeat(20);
+        let packed = compress_or_store(&text, &options).unwrap();
+        assert!(matches!(packed, Cow::Owned(_)));
+        assert!(packed.len() < text.len());
+        assert_eq!(decompress_or_stored(&packed).unwrap(), &text[..]);
+
+        // Too short to shrink, so stored as-is or escaped behind a marker
+        for input in [&b""[..], b"ab", b"\xc0ab", b"\xc1ab", b"\x7b\x9e\x20"] {
+            let packed = compress_or_store(input, &options).unwrap();
+            let escaped = matches!(input.first(), Some(0xc0 | 0xc1));
+            assert_eq!(matches!(packed, Cow::Borrowed(_)), !escaped);
+            assert_eq!(decompress_or_stored(&packed).unwrap(), input);
+        }
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..options
+        };
+        assert_eq!(compress_or_store(&text, &raw), Err(Error::InvalidMode));
+    }
+
+    #[test]
+    fn test_raw_backward_round_trip() {
+        let original = b"Backward raw block test data, backward raw block test data.";
+        for (version, decompress_backward) in [
+            (
+                Version::V1,
+                decompress_v1_backward as fn(&[u8]) -> Result<Vec<u8>>,
+            ),
+            (Version::V2, decompress_v2_backward),
+        ] {
+            let options = Options {
+                version,
+                mode: Mode::RawBackward,
+                ..Options::default()
+            };
+            let compressed = compress_with_options(original, &options).unwrap();
+            let decompressed = decompress_backward(&compressed).unwrap();
+            assert_eq!(original, decompressed.as_slice());
+        }
+    }
+
+    /// 64 KiB of xorshift32 output: too random for either format to shrink,
+    /// so it is stored whatever the compressor's heuristics
+    fn noise_block() -> Vec<u8> {
+        let mut state = 0x1234_5678u32;
+        (0..BLOCK_SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_struct_layout_matches_c() {
+        use core::mem::{align_of, offset_of, size_of};
+
+        let mut offsets = [usize::MAX; 6];
+        let mut align = 0;
+        let size = unsafe { lzsa_options_layout(offsets.as_mut_ptr(), offsets.len(), &mut align) };
+        assert_eq!(size, size_of::<Options>());
+        assert_eq!(align, align_of::<Options>());
+        assert_eq!(
+            offsets,
+            [
+                offset_of!(Options, version),
+                offset_of!(Options, mode),
+                offset_of!(Options, quality),
+                offset_of!(Options, min_match_size),
+                offset_of!(Options, effort),
+                offset_of!(Options, block_size),
+            ]
+        );
+
+        let mut offsets = [usize::MAX; 6];
+        let size = unsafe { lzsa_stats_layout(offsets.as_mut_ptr(), offsets.len(), &mut align) };
+        assert_eq!(size, size_of::<RawStats>());
+        assert_eq!(align, align_of::<RawStats>());
+        assert_eq!(
+            offsets,
+            [
+                offset_of!(RawStats, input_size),
+                offset_of!(RawStats, output_size),
+                offset_of!(RawStats, block_count),
+                offset_of!(RawStats, command_count),
+                offset_of!(RawStats, match_count),
+                offset_of!(RawStats, literal_count),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compress_with_progress() {
+        let original: Vec<u8> = (0..150_000u32)
+            .map(|i| (i % 251) as u8 ^ (i / 1000) as u8)
+            .collect();
+        let options = Options {
+            block_size: 40_000,
+            ..Options::default()
+        };
+
+        let mut calls = Vec::new();
+        let compressed =
+            compress_with_progress(&original, &options, |done, total| calls.push((done, total)))
+                .unwrap();
+        assert_eq!(
+            calls,
+            [
+                (40_000, 150_000),
+                (80_000, 150_000),
+                (120_000, 150_000),
+                (150_000, 150_000)
+            ]
+        );
+        assert_eq!(decompress(&compressed).unwrap(), original);
+
+        let mut writer = stream::LzsaWriter::new(Vec::new(), options).unwrap();
+        std::io::Write::write_all(&mut writer, &original).unwrap();
+        assert_eq!(writer.finish().unwrap(), compressed);
+
+        // No calls on error or for empty input
+        let invalid = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        let mut called = false;
+        assert_eq!(
+            compress_with_progress(&original, &invalid, |_, _| called = true),
+            Err(Error::InvalidMinMatch)
+        );
+        assert!(
+            compress_with_progress(&[], &options, |_, _| called = true)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(!called);
+    }
+
+    #[test]
+    fn test_compress_vectored() {
+        let original: Vec<u8> = (0..100_000u32)
+            .map(|i| (i % 253) as u8 ^ (i / 700) as u8)
+            .collect();
+        let options = Options {
+            block_size: 30_000,
+            ..Options::default()
+        };
+        let expected = compress_with_progress(&original, &options, |_, _| {}).unwrap();
+
+        // Chunk boundaries that split, match and straddle blocks
+        let (a, rest) = original.split_at(17);
+        let (b, rest) = rest.split_at(29_983);
+        let (c, d) = rest.split_at(45_001);
+        let compressed = compress_vectored(&[a, &[], b, c, d], &options).unwrap();
+        assert_eq!(compressed, expected);
+        assert_eq!(decompress(&compressed).unwrap(), original);
+
+        let single = compress_vectored(&[&original], &options).unwrap();
+        assert_eq!(single, expected);
+        assert!(compress_vectored(&[&[], &[]], &options).unwrap().is_empty());
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(
+            compress_vectored(&[b"raw ", b"parts"], &raw).unwrap(),
+            compress_with_options(b"raw parts", &raw).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_into_vec() {
+        let original = b"Reused vectors, reused vectors, reused vectors.".repeat(50);
+        let options = Options::default();
+
+        let mut compressed = Vec::with_capacity(4096);
+        let capacity = compressed.capacity();
+        compressed.extend_from_slice(b"stale");
+        compress_into_vec(&original, &mut compressed, &options).unwrap();
+        assert_eq!(
+            compressed,
+            compress_with_options(&original, &options).unwrap()
+        );
+        assert_eq!(compressed.capacity(), capacity);
+
+        let mut decompressed = vec![0xff; BLOCK_SIZE];
+        let capacity = decompressed.capacity();
+        decompress_into_vec(&compressed, &mut decompressed).unwrap();
+        assert_eq!(decompressed, original);
+        assert_eq!(decompressed.capacity(), capacity);
+
+        // Empty input and errors leave the vectors empty
+        compress_into_vec(&[], &mut compressed, &options).unwrap();
+        assert!(compressed.is_empty());
+        let invalid = Options {
+            min_match_size: 0,
+            ..options
+        };
+        assert_eq!(
+            compress_into_vec(&original, &mut compressed, &invalid),
+            Err(Error::InvalidMinMatch)
+        );
+        assert!(compressed.is_empty());
+        assert!(decompress_into_vec(b"garbage", &mut decompressed).is_err());
+        assert!(decompressed.is_empty());
+    }
+
+    #[test]
+    fn test_compressed_size() {
+        let text = b"Measured, not kept. Measured, not kept.".repeat(40);
+        for options in [
+            Options::default(),
+            Options {
+                version: Version::V2,
+                block_size: 256,
+                ..Options::default()
+            },
+            Options {
+                mode: Mode::RawBackward,
+                ..Options::default()
+            },
+        ] {
+            for input in [&text[..], &noise_block(), b""] {
+                assert_eq!(
+                    compressed_size(input, &options),
+                    compress_with_options(input, &options).map(|output| output.len())
+                );
+            }
+        }
+        let invalid = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(
+            compressed_size(&text, &invalid),
+            Err(Error::InvalidMinMatch)
+        );
+    }
+
+    #[test]
+    fn test_would_expand() {
+        let options = Options::default();
+        let text = b"Expand or not, expand or not, expand or not.".repeat(20);
+        assert!(!would_expand(&text, &options).unwrap());
+        assert!(would_expand(&noise_block(), &options).unwrap());
+        assert!(would_expand(b"", &options).unwrap());
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..options
+        };
+        assert!(!would_expand(&text, &raw).unwrap());
+        let invalid = Options {
+            min_match_size: 0,
+            ..options
+        };
+        assert_eq!(would_expand(&text, &invalid), Err(Error::InvalidMinMatch));
+    }
+
+    #[test]
+    fn test_decompress_with_version_out() {
+        let original = b"Version out, version out, version out.";
+        for version in [Version::V1, Version::V2] {
+            let options = Options {
+                version,
+                ..Options::default()
+            };
+            let compressed = compress_with_options(original, &options).unwrap();
+            assert_eq!(
+                decompress_with_version_out(&compressed).unwrap(),
+                (original.to_vec(), Some(version))
+            );
+        }
+        assert_eq!(decompress_with_version_out(&[]).unwrap(), (vec![], None));
+        assert!(decompress_with_version_out(b"garbage").is_err());
+    }
+
+    #[test]
+    fn test_version_order() {
+        assert!(Version::V2 > Version::V1);
+        assert_eq!(Version::V1.max(Version::V2), Version::V2);
+        assert_eq!(
+            Version::V1.cmp(&Version::V2),
+            (Version::V1 as i32).cmp(&(Version::V2 as i32))
+        );
+    }
+
+    #[test]
+    fn test_frame_magic() {
+        let original = b"Magic bytes, magic bytes, magic bytes.";
+        assert!(
+            compress_v1(original)
+                .unwrap()
+                .starts_with(Version::V1.frame_magic())
+        );
+        assert!(
+            compress_v2(original)
+                .unwrap()
+                .starts_with(Version::V2.frame_magic())
+        );
+
+        for version in [Version::V1, Version::V2] {
+            let mut header = [0u8; HEADER_SIZE];
+            let mut header_size = HEADER_SIZE;
+            let result = unsafe {
+                lzsa_encode_stream_header(header.as_mut_ptr(), &mut header_size, version as c_int)
+            };
+            assert_eq!(result, 0);
+            assert_eq!(&header[..header_size], version.frame_magic());
+            assert_eq!(detect_version(version.frame_magic()), Ok(version));
+        }
+    }
+
+    #[test]
+    fn test_decompress_streaming() {
+        let original: Vec<u8> = (0..200_000u32)
+            .map(|i| (i % 241) as u8 ^ (i / 5000) as u8)
+            .collect();
+        let compressed = compress_v2(&original).unwrap();
+
+        let mut output = Vec::new();
+        decompress_streaming(&compressed, |block| {
+            assert!(!block.is_empty() && block.len() <= BLOCK_SIZE);
+            output.extend_from_slice(block);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(output, original);
+
+        // The sink's error ends decoding after the first block
+        let mut calls = 0;
+        let result = decompress_streaming(&compressed, |_| {
+            calls += 1;
+            Err(Error::Io(std::io::ErrorKind::WriteZero))
+        });
+        assert_eq!(result, Err(Error::Io(std::io::ErrorKind::WriteZero)));
+        assert_eq!(calls, 1);
+
+        let mut seen = 0;
+        let result = decompress_streaming(&compressed[..compressed.len() - 1], |block| {
+            seen += block.len();
+            Ok(())
+        });
+        assert_eq!(result, Err(Error::Truncated));
+        assert_eq!(seen, original.len());
+    }
+
+    #[test]
+    fn test_decompress_prefix() {
+        let original: Vec<u8> = (0..200_000u32)
+            .map(|i| (i % 241) as u8 ^ (i / 5000) as u8)
+            .collect();
+        let compressed = compress_v1(&original).unwrap();
+
+        for max_bytes in [0, 1, 300, BLOCK_SIZE, BLOCK_SIZE + 1, original.len()] {
+            assert_eq!(
+                decompress_prefix(&compressed, max_bytes).unwrap(),
+                &original[..max_bytes]
+            );
+        }
+        assert_eq!(
+            decompress_prefix(&compressed, usize::MAX).unwrap(),
+            original
+        );
+
+        // Only the first block is decoded, so damage after it goes unseen
+        let (first_size, _) = decode_block_frame(&compressed[HEADER_SIZE..]).unwrap();
+        let cut = HEADER_SIZE + FRAME_SIZE + first_size;
+        assert_eq!(
+            decompress_prefix(&compressed[..cut + 1], 300).unwrap(),
+            &original[..300]
+        );
+        assert_eq!(
+            decompress_prefix(&compressed[..cut + 1], BLOCK_SIZE + 1),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_context_pool() {
+        let options = Options::default();
+        let pool = ContextPool::new(options, 2).unwrap();
+        assert_eq!(pool.idle_count(), 1);
+
+        // A large message first, then a small one: the small one compresses as
+        // on a fresh context, with nothing left over from the first
+        let large = b"Pooled contexts, pooled contexts. ".repeat(3000);
+        let small = b"small, small, small";
+        assert_eq!(decompress(&pool.compress(&large).unwrap()).unwrap(), large);
+        assert_eq!(
+            pool.compress(small).unwrap(),
+            Context::new(options).unwrap().compress(small).unwrap()
+        );
+
+        {
+            let mut first = pool.get().unwrap();
+            let mut second = pool.get().unwrap();
+            let mut third = pool.get().unwrap();
+            assert_eq!(pool.idle_count(), 0);
+            for context in [&mut first, &mut second, &mut third] {
+                let compressed = context.compress(small).unwrap().to_vec();
+                assert_eq!(context.decompress(&compressed).unwrap(), small);
+            }
+        }
+        // Only max_idle contexts are kept
+        assert_eq!(pool.idle_count(), 2);
+
+        let invalid = Options {
+            min_match_size: 0,
+            ..options
+        };
+        assert_eq!(
+            ContextPool::new(invalid, 2).unwrap_err(),
+            Error::InvalidMinMatch
+        );
+    }
+
+    #[test]
+    fn test_options_display() {
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+        assert_eq!(options.to_string(), "LZSA2 normal ratio min_match=3");
+
+        let tuned = Options {
+            mode: Mode::RawBackward,
+            quality: Quality::Speed,
+            min_match_size: 5,
+            effort: 4,
+            block_size: 4096,
+            ..Options::default()
+        };
+        assert_eq!(
+            tuned.to_string(),
+            "LZSA1 raw-backward speed min_match=5 effort=4 block_size=4096"
+        );
+        assert_eq!(Mode::RawForward.to_string(), "raw-forward");
+    }
+
+    #[test]
+    fn test_frame_sink() {
+        let original: Vec<u8> = (0..90_000u32)
+            .map(|i| (i % 211) as u8 ^ (i / 3000) as u8)
+            .collect();
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+
+        let mut sink = FrameSink::new(options);
+        assert!(sink.is_empty());
+        for chunk in original.chunks(7_001) {
+            sink.push(chunk);
+        }
+        assert_eq!(sink.len(), original.len());
+        let compressed = sink.finish().unwrap();
+        assert_eq!(
+            compressed,
+            compress_with_options(&original, &options).unwrap()
+        );
+        assert_eq!(decompress(&compressed).unwrap(), original);
+
+        assert!(FrameSink::new(options).finish().unwrap().is_empty());
+        let mut invalid = FrameSink::new(Options {
+            min_match_size: 0,
+            ..options
+        });
+        invalid.push(b"data");
+        assert_eq!(invalid.finish(), Err(Error::InvalidMinMatch));
+    }
+
+    #[test]
+    fn test_max_compressed_size_const() {
+        const _: () = assert!(max_compressed_size_const(0, Version::V1) == 6);
+        let sizes = (0..200).chain([BLOCK_SIZE - 1, BLOCK_SIZE, BLOCK_SIZE + 1, 10 * BLOCK_SIZE]);
+        for input_len in sizes.chain((0..50).map(|i| i * 12_345)) {
+            for version in [Version::V1, Version::V2] {
+                for mode in [Mode::Normal, Mode::RawForward, Mode::RawBackward] {
+                    assert!(
+                        max_compressed_size_const(input_len, version)
+                            >= max_compressed_size(input_len, version, mode),
+                        "{input_len} {version:?} {mode:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compress_smart() {
+        let options = Options::default();
+        let record = b"0123456789abcdef".repeat(4);
+        for len in 0..=record.len() {
+            let input = &record[..len];
+            let compressed = compress_smart(input, &options).unwrap();
+            assert_eq!(decompress_smart(&compressed).unwrap(), input);
+            if (1..MIN_STREAM_SIZE).contains(&len) {
+                assert_eq!(compressed.len(), len + 1);
+                assert!(compressed.len() <= compress_with_options(input, &options).unwrap().len());
+            } else {
+                assert_eq!(compressed, compress_with_options(input, &options).unwrap());
+            }
+        }
+        assert_eq!(compress_smart(b"ab", &options).unwrap(), [0x00, b'a', b'b']);
+
+        // Nothing below the threshold can compress any smaller
+        for byte in [0u8, 0x7b, 0xff] {
+            let input = [byte; MIN_STREAM_SIZE - 1];
+            let compressed = compress_with_options(&input, &options).unwrap();
+            assert!(compressed.len() >= MIN_STREAM_SIZE);
+        }
+
+        assert_eq!(
+            decompress_smart(&[0x00; MIN_STREAM_SIZE + 1]),
+            Err(Error::InvalidFormat)
+        );
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..options
+        };
+        assert_eq!(compress_smart(b"ab", &raw), Err(Error::InvalidMode));
+    }
+
+    #[test]
+    fn test_find_frames() {
+        let first = compress_v1(b"first frame, first frame").unwrap();
+        let second = compress_v2(b"second frame, second frame").unwrap();
+        let mut file = vec![0x7b, 0x9e, 0x40, 0x7b];
+        let first_at = file.len();
+        file.extend_from_slice(&first);
+        file.extend_from_slice(b"\x7b\x9egarbage");
+        let second_at = file.len();
+        file.extend_from_slice(&second);
+
+        let offsets = find_frames(&file);
+        assert!(offsets.contains(&first_at) && offsets.contains(&second_at));
+        assert!(!offsets.contains(&0));
+        assert_eq!(
+            decompress(&file[first_at..]).unwrap(),
+            b"first frame, first frame"
+        );
+        assert_eq!(
+            decompress(&file[second_at..]).unwrap(),
+            b"second frame, second frame"
+        );
+
+        assert!(find_frames(&[]).is_empty());
+        assert!(find_frames(&FRAME_MAGIC).is_empty());
+    }
+
+    #[test]
+    fn test_decompress_known() {
+        let original = b"Known versions, known versions, known versions.";
+        let v1 = compress_v1(original).unwrap();
+        let v2 = compress_v2(original).unwrap();
+
+        assert_eq!(decompress_known(&v1, Version::V1).unwrap(), original);
+        assert_eq!(decompress_known(&v2, Version::V2).unwrap(), original);
+        assert_eq!(
+            decompress_known(&v1, Version::V2),
+            Err(Error::InvalidVersion)
+        );
+        assert_eq!(
+            decompress_known(&v2, Version::V1),
+            Err(Error::InvalidVersion)
+        );
+        assert_eq!(
+            decompress_known(b"garbage", Version::V1),
+            Err(Error::InvalidFormat)
+        );
+        assert!(decompress_known(&[], Version::V2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_allocation_failure() {
+        let block = compress_raw(b"raw, raw, raw, raw", Version::V1).unwrap();
+        // Too large for any allocator, and past what a Vec can hold
+        for expected_len in [isize::MAX as usize, usize::MAX] {
+            assert_eq!(
+                decompress_raw(&block, Version::V1, expected_len),
+                Err(Error::OutOfMemory)
+            );
+        }
+
+        let mut buffer = b"kept".to_vec();
+        assert_eq!(try_resize(&mut buffer, usize::MAX), Err(Error::OutOfMemory));
+        assert_eq!(buffer, b"kept");
+        try_resize(&mut buffer, 2).unwrap();
+        assert_eq!(buffer, b"ke");
+    }
+
+    #[test]
+    fn test_block_v2_round_trip() {
+        let original = b"One bare block, one bare block, one bare block.".repeat(10);
+
+        let block = compress_block_v2(&original, &BlockOptions::default()).unwrap();
+        assert_eq!(
+            decompress_block_v2(&block, original.len()).unwrap(),
+            original
+        );
+
+        // The same bytes as the block inside a single-block framed stream
+        let options = Options {
+            version: Version::V2,
+            min_match_size: MIN_MATCH_V2,
+            ..Options::default()
+        };
+        let framed = compress_with_options(&original, &options).unwrap();
+        assert_eq!(
+            &framed[HEADER_SIZE + FRAME_SIZE..framed.len() - FRAME_SIZE],
+            block
+        );
+
+        let backward = BlockOptions {
+            backward: true,
+            ..BlockOptions::default()
+        };
+        let block = compress_block_v2(&original, &backward).unwrap();
+        assert_eq!(
+            decompress_block_v2_with(&block, original.len(), &backward).unwrap(),
+            original
+        );
+
+        assert_eq!(decompress_block_v2(&[], 0).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_block_v2_history() {
+        let first = b"Shared history between blocks, shared history.".repeat(4);
+        let second = b"Shared history between blocks!".to_vec();
+
+        for backward in [false, true] {
+            let options = BlockOptions {
+                backward,
+                history: &first,
+                ..BlockOptions::default()
+            };
+            let with_history = compress_block_v2(&second, &options).unwrap();
+            let backward_only = BlockOptions {
+                backward,
+                ..BlockOptions::default()
+            };
+            let without = compress_block_v2(&second, &backward_only).unwrap();
+            assert!(with_history.len() < without.len());
+
+            assert_eq!(
+                decompress_block_v2_with(&with_history, second.len(), &options).unwrap(),
+                second
+            );
+            assert_ne!(
+                decompress_block_v2_with(&with_history, second.len(), &backward_only),
+                Ok(second.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_block_at() {
+        let first = b"Shared history between blocks, shared history.".repeat(4);
+        let second = b"Shared history between blocks!".to_vec();
+        let first_block = compress_block_v2(&first, &BlockOptions::default()).unwrap();
+        let options = BlockOptions {
+            history: &first,
+            ..BlockOptions::default()
+        };
+        let second_block = compress_block_v2(&second, &options).unwrap();
+
+        let mut output = vec![0u8; first.len() + second.len()];
+        let written = decompress_block_at(&first_block, &mut output, 0, Version::V2).unwrap();
+        assert_eq!(written, first.len());
+        let written =
+            decompress_block_at(&second_block, &mut output, first.len(), Version::V2).unwrap();
+        assert_eq!(written, second.len());
+        assert_eq!(output[..first.len()], first[..]);
+        assert_eq!(output[first.len()..], second[..]);
+
+        assert_eq!(
+            decompress_block_at(&[], &mut output, first.len(), Version::V2),
+            Ok(0)
+        );
+        let len = output.len();
+        assert_eq!(
+            decompress_block_at(&second_block, &mut output, len + 1, Version::V2),
+            Err(Error::OutOfRange)
+        );
+        assert!(decompress_block_at(&second_block, &mut output, len - 1, Version::V2).is_err());
+    }
+
+    #[test]
+    fn test_block_output_limit_with_history() {
+        // The output limit counts from the end of the history: a block that
+        // decodes to more than the room left must fail, not write past the
+        // window (the asan feature catches such writes)
+        let history: Vec<u8> = (0..BLOCK_SIZE as u32).map(|i| (i % 251) as u8).collect();
+        let data = history[..4096].to_vec();
+        let options = BlockOptions {
+            history: &history,
+            ..BlockOptions::default()
+        };
+        let block = compress_block_v2(&data, &options).unwrap();
+        assert!(decompress_block_v2_with(&block, 16, &options).is_err());
+        assert_eq!(
+            decompress_block_v2_with(&block, data.len(), &options).unwrap(),
+            data
+        );
+
+        // Allocated to the exact size, so a write past it is out of bounds
+        let mut output = vec![0u8; BLOCK_SIZE + 16];
+        output[..BLOCK_SIZE].copy_from_slice(&history);
+        assert!(decompress_block_at(&block, &mut output, BLOCK_SIZE, Version::V2).is_err());
+    }
+
+    #[test]
+    fn test_block_v2_errors() {
+        let input = vec![0u8; BLOCK_SIZE + 1];
+        assert_eq!(
+            compress_block_v2(&input, &BlockOptions::default()),
+            Err(Error::InvalidBlockSize)
+        );
+        assert_eq!(
+            decompress_block_v2(&[], BLOCK_SIZE + 1),
+            Err(Error::InvalidBlockSize)
+        );
+
+        let options = BlockOptions {
+            min_match_size: 1,
+            ..BlockOptions::default()
+        };
+        assert_eq!(
+            compress_block_v2(b"data", &options),
+            Err(Error::InvalidMinMatch)
+        );
+
+        let original = b"Exact length, exact length, exact length.";
+        let block = compress_block_v2(original, &BlockOptions::default()).unwrap();
+        assert_eq!(
+            decompress_block_v2(&block, original.len() + 1),
+            Err(Error::DecompressionFailed)
+        );
+    }
+
+    #[test]
+    fn test_min_match_with_quality() {
+        let original = b"Minimum matches at speed, minimum matches at speed.".repeat(30);
+        for version in [Version::V1, Version::V2] {
+            for quality in [Quality::Speed, Quality::Ratio] {
+                for min_match_size in 0..8 {
+                    let options = Options {
+                        version,
+                        quality,
+                        min_match_size,
+                        ..Options::default()
+                    };
+                    // The range is checked the same way for both qualities
+                    let result = compress_with_options(&original, &options);
+                    if version.min_match_sizes().contains(&min_match_size) {
+                        assert_eq!(decompress(&result.unwrap()).unwrap(), original);
+                    } else {
+                        assert_eq!(result, Err(Error::InvalidMinMatch));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mode_version_matrix() {
+        let original = b"Every mode with every version, every mode with every version.";
+        for version in [Version::V1, Version::V2] {
+            for mode in [Mode::Normal, Mode::RawForward, Mode::RawBackward] {
+                let options = Options {
+                    version,
+                    mode,
+                    min_match_size: MIN_MATCH_V1,
+                    ..Options::default()
+                };
+                let compressed = compress_with_options(original, &options).unwrap();
+                assert_eq!(
+                    decompress_matching(&compressed, original.len(), &options).unwrap(),
+                    original,
+                    "{version:?} {mode:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_raw_backward_too_large() {
+        let options = Options {
+            mode: Mode::RawBackward,
+            ..Options::default()
+        };
+        let input = vec![0u8; BLOCK_SIZE + 1];
+        assert_eq!(
+            compress_with_options(&input, &options),
+            Err(Error::InvalidBlockSize)
+        );
+        assert_eq!(
+            compress_raw(&input, Version::V2),
+            Err(Error::InvalidBlockSize)
+        );
+
+        // A raw block may still be exactly the maximum
+        let input = &input[..BLOCK_SIZE];
+        assert!(compress_with_options(input, &options).is_ok());
+    }
+
+    #[test]
+    fn test_one_byte_over_block_size() {
+        let original: Vec<u8> = (0..BLOCK_SIZE as u32 + 1)
+            .map(|i| (i % 251) as u8 ^ (i >> 10) as u8)
+            .collect();
+        let (compressed, stats) = compress_with_stats(&original, &Options::default()).unwrap();
+        assert_eq!(stats.block_count, 2);
+        assert_eq!(decompress(&compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_compress_into() {
+        let original = b"Compress into a caller buffer, compress into a caller buffer.";
+        let options = Options::default();
+
+        let mut output = [0u8; 256];
+        let written = compress_into(original, &mut output, &options).unwrap();
+        assert_eq!(
+            &output[..written],
+            compress_with_options(original, &options)
+                .unwrap()
+                .as_slice()
+        );
+        let decompressed = decompress(&output[..written]).unwrap();
+        assert_eq!(original, decompressed.as_slice());
+
+        let mut tiny = [0u8; 4];
+        assert_eq!(
+            compress_into(original, &mut tiny, &options),
+            Err(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_max_compressed_size() {
+        assert_eq!(max_compressed_size(0, Version::V1, Mode::Normal), 6);
+        assert_eq!(max_compressed_size(1, Version::V1, Mode::Normal), 10);
+        assert_eq!(
+            max_compressed_size(BLOCK_SIZE + 1, Version::V2, Mode::Normal),
+            BLOCK_SIZE + 1 + 12
+        );
+
+        // Poorly compressible input must still fit the bound
+        let noise: Vec<u8> = (0..5000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let size = max_compressed_size(noise.len(), Version::V2, Mode::Normal);
+        let mut output = vec![0u8; size];
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+        assert!(compress_into(&noise, &mut output, &options).is_ok());
+    }
+
+    #[test]
+    fn test_max_decompressed_size() {
+        let original = vec![7u8; BLOCK_SIZE + 100];
+        let compressed = compress_v2(&original).unwrap();
+        let max_size = max_decompressed_size(&compressed).unwrap();
+        assert!(max_size >= original.len());
+
+        assert_eq!(max_decompressed_size(b"garbage"), Err(Error::InvalidFormat));
+        assert_eq!(max_decompressed_size(&[]), Err(Error::InvalidFormat));
+    }
+
+    #[test]
+    fn test_decompress_limited() {
+        let original = b"Bounded output, bounded output, bounded output.".repeat(30);
+        let compressed = compress_v2(&original).unwrap();
+
+        assert_eq!(
+            decompress_limited(&compressed, original.len()).unwrap(),
+            original
+        );
+        assert_eq!(
+            decompress_limited(&compressed, original.len() - 1),
+            Err(Error::OutputTooLarge)
+        );
+        assert_eq!(
+            decompress_limited(&compressed, 0),
+            Err(Error::OutputTooLarge)
+        );
+        assert_eq!(
+            decompress_limited(b"garbage", 1024),
+            Err(Error::InvalidFormat)
+        );
+        assert!(decompress_limited(&[], 0).unwrap().is_empty());
+
+        // A block that cannot decode is corrupt, not too large: its only
+        // token announces an extended literal length that is missing
+        let corrupt = [0x7b, 0x9e, 0x00, 0x01, 0x00, 0x00, 0xff, 0, 0, 0];
+        assert_eq!(
+            decompress_limited(&corrupt, 16),
+            Err(Error::DecompressionFailed)
+        );
+
+        // Incompressible data is stored, so its size is known up front
+        let mut state = 0x2545_f491_u32;
+        let noise: Vec<u8> = (0..1000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let stored = compress_v2(&noise).unwrap();
+        assert!(parse_frame_header(&stored).unwrap().uncompressed);
+        assert_eq!(decompress_limited(&stored, 999), Err(Error::OutputTooLarge));
+        assert_eq!(decompress_limited(&stored, 1000).unwrap(), noise);
+    }
+
+    type RawDecompressFn =
+        unsafe extern "C" fn(*const u8, usize, *mut u8, *mut usize, c_int) -> c_int;
+
+    /// Test-only stand-in for `lzsa_decompress_raw` that claims one byte
+    /// more output than the buffer holds
+    unsafe extern "C" fn oversized_wrapper(
+        _input: *const u8,
+        _input_size: usize,
+        _output: *mut u8,
+        output_size: *mut usize,
+        _version: c_int,
+    ) -> c_int {
+        unsafe { *output_size += 1 };
+        0
+    }
+
+    /// Test-only stand-in returning a positive length instead of 0
+    unsafe extern "C" fn positive_wrapper(
+        _input: *const u8,
+        _input_size: usize,
+        _output: *mut u8,
+        output_size: *mut usize,
+        _version: c_int,
+    ) -> c_int {
+        unsafe { *output_size as c_int }
+    }
+
+    #[test]
+    fn test_checked_ffi() {
+        let input = b"not really a block";
+        let mut output = [0u8; 10];
+
+        for wrapper in [oversized_wrapper as RawDecompressFn, positive_wrapper] {
+            let mut output_size = output.len();
+            let result = unsafe {
+                wrapper(
+                    input.as_ptr(),
+                    input.len(),
+                    output.as_mut_ptr(),
+                    &mut output_size,
+                    Version::V2 as c_int,
+                )
+            };
+            assert_eq!(
+                checked_decompress(result, output_size, output.len()),
+                Err(Error::DecompressionFailed)
+            );
+            assert_eq!(
+                checked_compress(result, output_size, output.len()),
+                Err(Error::CompressionFailed)
+            );
+        }
+
+        assert_eq!(checked_decompress(0, 10, 10), Ok(10));
+        // Error codes still map as before, whatever the size says
+        assert_eq!(
+            checked_compress(-4, usize::MAX, 10),
+            Err(Error::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_short_garbage_input() {
+        let header = &compress_v2(b"just the header").unwrap()[..HEADER_SIZE];
+        let inputs: [&[u8]; 4] = [&[0x7b], &[0x7b, 0x9e], &[0xff, 0x00, 0x42], header];
+
+        for input in inputs {
+            assert_eq!(decompress(input), Err(Error::InvalidFormat));
+            assert_eq!(decompress_v1(input), Err(Error::InvalidFormat));
+            assert_eq!(decompress_v2(input), Err(Error::InvalidFormat));
+            assert_eq!(decompress_detect(input), Err(Error::InvalidFormat));
+            assert_eq!(
+                decompress_with_dictionary(input, b"dictionary"),
+                Err(Error::InvalidFormat)
+            );
+            assert_eq!(
+                decompress_into(input, &mut [0u8; 64]),
+                Err(Error::InvalidFormat)
+            );
+            assert_eq!(
+                Decompressor::new().decompress(input).err(),
+                Some(Error::InvalidFormat)
+            );
+            assert_eq!(max_decompressed_size(input), Err(Error::InvalidFormat));
+        }
+    }
+
+    #[test]
+    fn test_decompress_into() {
+        let original = b"Fixed-size record, fixed-size record, fixed-size record.";
+        let compressed = compress_v2(original).unwrap();
+
+        let mut output = [0u8; 64];
+        let written = decompress_into(&compressed, &mut output).unwrap();
+        assert_eq!(&output[..written], original.as_slice());
+
+        let mut tiny = [0u8; 8];
+        assert_eq!(
+            decompress_into(&compressed, &mut tiny),
+            Err(Error::BufferTooSmall)
+        );
+        assert_eq!(
+            decompress_into(b"garbage", &mut output),
+            Err(Error::DecompressionFailed)
+        );
+
+        // A short buffer is not blamed for a block that cannot decode
+        let corrupt = [0x7b, 0x9e, 0x00, 0x01, 0x00, 0x00, 0xff, 0, 0, 0];
+        assert_eq!(
+            decompress_into(&corrupt, &mut tiny),
+            Err(Error::DecompressionFailed)
+        );
+    }
+
+    #[test]
+    fn test_decompress_in_place() {
+        let original = b"Loaded to the tail, unpacked to the front. ".repeat(3000);
+        let compressed = compress_v2(&original).unwrap();
+
+        // Compressed data at the end of a buffer that already has room
+        let mut buffer = vec![0u8; original.len() + BLOCK_SIZE];
+        let tail = buffer.len() - compressed.len();
+        buffer[tail..].copy_from_slice(&compressed);
+        assert_eq!(
+            decompress_in_place(&mut buffer, compressed.len()),
+            Ok(original.len())
+        );
+        assert_eq!(buffer, original);
+
+        // A buffer holding only the compressed data grows as needed
+        let mut buffer = compressed.clone();
+        let len = compressed.len();
+        assert_eq!(decompress_in_place(&mut buffer, len), Ok(original.len()));
+        assert_eq!(buffer, original);
+
+        let mut buffer = compressed.clone();
+        assert_eq!(
+            decompress_in_place(&mut buffer, len + 1),
+            Err(Error::BufferTooSmall)
+        );
+        let mut empty = vec![1u8; 4];
+        assert_eq!(decompress_in_place(&mut empty, 0), Ok(0));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_decompress_from_reader() {
+        use std::io::Read;
+
+        let original = vec![b'r'; BLOCK_SIZE + 123];
+        let mut input = compress_v2(&original).unwrap();
+        input.extend_from_slice(b"trailing");
+
+        let mut reader = std::io::Cursor::new(&input);
+        assert_eq!(decompress_from_reader(&mut reader).unwrap(), original);
+
+        // The bytes after the stream are still in the reader
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"trailing");
+
+        let truncated = &input[..input.len() - 12];
+        assert_eq!(
+            decompress_from_reader(&mut &truncated[..]),
+            Err(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        );
+        assert_eq!(
+            decompress_from_reader(&mut &b"garbage"[..]),
+            Err(Error::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_decompress_detect() {
+        let original = b"Which version made this? Which version made this?";
+
+        let (data, version) = decompress_detect(&compress_v1(original).unwrap()).unwrap();
+        assert_eq!(data, original);
+        assert_eq!(version, Version::V1);
+
+        let (data, version) = decompress_detect(&compress_v2(original).unwrap()).unwrap();
+        assert_eq!(data, original);
+        assert_eq!(version, Version::V2);
+
+        assert_eq!(decompress_detect(&[]), Err(Error::InvalidFormat));
+    }
+
+    #[test]
+    fn test_compress_with_stats() {
+        let original = b"Statistics, statistics, statistics, statistics, statistics.".repeat(50);
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+
+        let (compressed, stats) = compress_with_stats(&original, &options).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), original);
+        assert_eq!(stats.input_len, original.len());
+        assert_eq!(stats.output_len, compressed.len());
+        assert_eq!(stats.block_count, 1);
+        assert!(stats.command_count > 0);
+        assert!(stats.match_count > 0 && stats.match_count <= stats.command_count);
+        assert!(stats.literal_count > 0 && stats.literal_count < original.len());
+
+        // A block that fails to shrink is stored and adds nothing
+        let (_, stats) = compress_with_stats(&noise_block(), &options).unwrap();
+        assert_eq!(
+            (stats.command_count, stats.match_count, stats.literal_count),
+            (0, 0, 0)
+        );
+
+        let (compressed, stats) = compress_with_stats(&[], &options).unwrap();
+        assert!(compressed.is_empty());
+        assert_eq!(stats.block_count, 0);
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(
+            compress_with_stats(&original, &raw),
+            Err(Error::InvalidMode)
+        );
+    }
+
+    #[test]
+    fn test_string_round_trip() {
+        let text = "Grüße, 世界! Text with multi-byte characters, text with multi-byte characters.";
+        let compressed = compress_str(text, &Options::default()).unwrap();
+        assert_eq!(decompress_to_string(&compressed).unwrap(), text);
+
+        let compressed = compress_v2(b"valid prefix \xff\xfe then garbage").unwrap();
+        assert_eq!(decompress_to_string(&compressed), Err(Error::InvalidUtf8));
+        assert_eq!(decompress_to_string(&[]).unwrap(), "");
+    }
+
+    #[test]
+    fn test_blocks() {
+        let first: Vec<u8> = (0..BLOCK_SIZE as u32 * 2 + 500)
+            .map(|i| (i % 251) as u8 ^ (i >> 12) as u8)
+            .collect();
+        let second = b"Second stream, second stream, second stream.".to_vec();
+
+        let mut input = compress_v1(&first).unwrap();
+        input.extend_from_slice(&compress_v2(&second).unwrap());
+
+        let decoded: Vec<Vec<u8>> = blocks(&input).collect::<Result<_>>().unwrap();
+        assert_eq!(decoded.len(), 4);
+        assert!(decoded.iter().all(|block| block.len() <= BLOCK_SIZE));
+        assert_eq!(decoded.concat(), [first, second].concat());
+
+        assert_eq!(blocks(&[]).count(), 0);
+
+        // A cut-off stream ends with one error
+        let mut iter = blocks(&input[..input.len() / 2]);
+        assert!(iter.next().unwrap().is_ok());
+        assert_eq!(iter.next(), Some(Err(Error::Truncated)));
+        assert_eq!(iter.next(), None);
+
+        let mut iter = blocks(b"not lzsa");
+        assert_eq!(iter.next(), Some(Err(Error::InvalidFormat)));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_decompress_all() {
+        let first = b"First stream, first stream, first stream.".to_vec();
+        let second = vec![b'x'; BLOCK_SIZE + 10];
+
+        let mut input = compress_v1(&first).unwrap();
+        input.extend_from_slice(&compress_v2(&second).unwrap());
+        input.extend_from_slice(&compress_v1(&first).unwrap());
+
+        let mut expected = first.clone();
+        expected.extend_from_slice(&second);
+        expected.extend_from_slice(&first);
+        assert_eq!(decompress_all(&input).unwrap(), expected);
+        assert_eq!(decompress_all(&[]).unwrap(), Vec::<u8>::new());
+
+        let mut trailing = input.clone();
+        trailing.extend_from_slice(b"junk");
+        assert_eq!(decompress_all(&trailing), Err(Error::InvalidFormat));
+
+        assert_eq!(
+            decompress_all(&input[..input.len() - 1]),
+            Err(Error::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_decompress_partial() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = compress_v2(&data).unwrap();
+        assert_eq!(decompress_partial(&compressed), (data.clone(), None));
+        assert_eq!(decompress_partial(&[]), (Vec::new(), None));
+
+        // Corrupt the third block's frame so it claims to be oversized
+        let mut pos = HEADER_SIZE;
+        for _ in 0..2 {
+            let (block_size, _) = decode_block_frame(&compressed[pos..]).unwrap();
+            pos += FRAME_SIZE + block_size;
+        }
+        let mut corrupt = compressed.clone();
+        corrupt[pos..pos + FRAME_SIZE].copy_from_slice(&[0xff, 0xff, 0x7f]);
+
+        let (output, error) = decompress_partial(&corrupt);
+        assert_eq!(output, &data[..2 * BLOCK_SIZE]);
+        assert_eq!(error, Some(Error::InvalidFormat));
+
+        let (output, error) = decompress_partial(b"not lzsa");
+        assert!(output.is_empty());
+        assert_eq!(error, Some(Error::InvalidFormat));
+    }
+
+    #[test]
+    fn test_append_frame() {
+        let entries: [&[u8]; 4] = [b"first log entry", b"", b"second log entry", &[7u8; 70_000]];
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+
+        let mut log = Vec::new();
+        for entry in entries {
+            let before = log.clone();
+            append_frame(&mut log, entry, &options).unwrap();
+            assert_eq!(log[..before.len()], before);
+        }
+        assert_eq!(decompress_all(&log).unwrap(), entries.concat());
+
+        // Failed appends leave the log as it was
+        let before = log.clone();
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..options
+        };
+        assert_eq!(
+            append_frame(&mut log, b"raw", &raw),
+            Err(Error::InvalidMode)
+        );
+        let bad = Options {
+            min_match_size: 0,
+            ..options
+        };
+        assert_eq!(
+            append_frame(&mut log, b"bad", &bad),
+            Err(Error::InvalidMinMatch)
+        );
+        assert_eq!(log, before);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data: Vec<u8> = (0..3 * BLOCK_SIZE as u32)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let compressed = compress_v2(&data).unwrap();
+
+        // Cut inside a block, inside a frame header and before the footer
+        let (first_block, _) = decode_block_frame(&compressed[HEADER_SIZE..]).unwrap();
+        for len in [
+            compressed.len() / 2,
+            HEADER_SIZE + FRAME_SIZE + first_block + 1,
+            compressed.len() - FRAME_SIZE,
+            compressed.len() - 1,
+        ] {
+            let partial = &compressed[..len];
+            assert_eq!(decompress(partial), Err(Error::Truncated), "len {len}");
+            assert_eq!(max_decompressed_size(partial), Err(Error::Truncated));
+            assert_eq!(
+                decompress_into(partial, &mut vec![0; data.len()]),
+                Err(Error::Truncated)
+            );
+            assert_eq!(
+                Decompressor::new().decompress(partial),
+                Err(Error::Truncated)
+            );
+        }
+
+        // Corruption is still told apart
+        assert_eq!(decompress(b"garbage"), Err(Error::DecompressionFailed));
+        assert_eq!(decompress(&compressed).unwrap(), data);
+
+        let error: std::io::Error = Error::Truncated.into();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_detect_version() {
+        let input = b"Route me by format, route me by format.";
+        assert_eq!(
+            detect_version(&compress_v1(input).unwrap()),
+            Ok(Version::V1)
+        );
+        assert_eq!(
+            detect_version(&compress_v2(input).unwrap()),
+            Ok(Version::V2)
+        );
+
+        // Only the header is needed
+        let compressed = compress_v2(input).unwrap();
+        assert_eq!(detect_version(&compressed[..3]), Ok(Version::V2));
+        assert_eq!(detect_version(&compressed[..2]), Err(Error::InvalidFormat));
+
+        let raw = compress_raw(input, Version::V2).unwrap();
+        assert_eq!(detect_version(&raw), Err(Error::InvalidFormat));
+    }
+
+    #[test]
+    fn test_parse_frame_header() {
+        let compressed = compress_v1(b"Header inspection, header inspection.").unwrap();
+        let header = parse_frame_header(&compressed).unwrap();
+        assert_eq!(header.version, Version::V1);
+        let block_size = header.block_size.unwrap();
+        assert!(block_size > 0 && block_size + 9 == compressed.len());
+        assert_eq!(header.original_size, None);
+
+        // Incompressible data is stored as-is
+        let noise: Vec<u8> = (0..256u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let header = parse_frame_header(&compress_v2(&noise).unwrap()).unwrap();
+        assert_eq!(header.version(), Version::V2);
+        assert!(header.uncompressed);
+        assert_eq!(header.block_size, Some(noise.len()));
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        let raw_block = compress_with_options(b"raw raw raw raw raw raw", &raw).unwrap();
+        assert_eq!(parse_frame_header(&raw_block), Err(Error::InvalidFormat));
+        assert_eq!(parse_frame_header(&[]), Err(Error::InvalidFormat));
+    }
+
+    #[test]
+    fn test_frame_header_original_size() {
+        // One stored block: its frame gives the size
+        let stored = [
+            0x7b, 0x9e, 0x20, 0x03, 0x00, 0x80, b'a', b'b', b'c', 0, 0, 0,
+        ];
+        let header = parse_frame_header(&stored).unwrap();
+        assert_eq!(header.original_size, Some(3));
+        // Without the footer it is unknown whether more blocks follow
+        assert_eq!(
+            parse_frame_header(&stored[..9]).unwrap().original_size,
+            None
+        );
+
+        let empty = [0x7b, 0x9e, 0x00, 0, 0, 0];
+        assert_eq!(parse_frame_header(&empty).unwrap().original_size, Some(0));
+        assert_eq!(
+            parse_frame_header(&empty[..HEADER_SIZE])
+                .unwrap()
+                .original_size,
+            None
+        );
+
+        // Several stored blocks
+        let options = Options {
+            block_size: 1,
+            ..Options::default()
+        };
+        let compressed = compress_with_options(b"two", &options).unwrap();
+        assert_eq!(parse_frame_header(&compressed).unwrap().original_size, None);
+    }
+
+    #[test]
+    fn test_original_size_corrupted() {
+        let stored = [
+            0x7b, 0x9e, 0x00, 0x05, 0x00, 0x80, b'a', b'b', b'c', b'd', b'e', 0, 0, 0,
+        ];
+        assert_eq!(decompress(&stored).unwrap(), b"abcde");
+
+        // A stored size that is off by one no longer lines up with the
+        // footer, so the header reports no size and the stream is truncated
+        for size in [0x04, 0x06] {
+            let mut corrupted = stored;
+            corrupted[3] = size;
+            assert_eq!(parse_frame_header(&corrupted).unwrap().original_size, None);
+            assert_eq!(decompress(&corrupted), Err(Error::Truncated));
+        }
+    }
+
+    #[test]
+    fn test_is_lzsa() {
+        let input = b"Sniff the file type, sniff the file type.";
+        let compressed = compress_v1(input).unwrap();
+        assert!(is_lzsa(&compressed));
+        assert!(is_lzsa(&compress_v2(input).unwrap()));
+
+        // A block frame claiming more data than follows it
+        assert!(!is_lzsa(&compressed[..compressed.len() / 2]));
+        assert!(!is_lzsa(&compressed[..HEADER_SIZE]));
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert!(!is_lzsa(&compress_with_options(input, &raw).unwrap()));
+        assert!(!is_lzsa(&[]));
+        assert!(!is_lzsa(b"\x7b"));
+        assert!(!is_lzsa(input));
+    }
+
+    #[test]
+    fn test_dictionary_round_trip() {
+        let dictionary = br#"{"id":0,"name":"","email":"","active":false,"tags":[]}"#;
+        let fragment =
+            br#"{"id":42,"name":"alice","email":"alice@example.com","active":true,"tags":[]}"#;
+        let options = Options::default();
+
+        let compressed = compress_with_dictionary(fragment, dictionary, &options).unwrap();
+        assert!(compressed.len() < compress_with_options(fragment, &options).unwrap().len());
+        assert_eq!(
+            decompress_with_dictionary(&compressed, dictionary).unwrap(),
+            fragment
+        );
+
+        // Without the dictionary the history is missing
+        assert_ne!(decompress(&compressed).ok().as_deref(), Some(&fragment[..]));
+        let other = br#"{"colour":"red","size":12,"weight":3.5,"flags":0}"#;
+        assert_ne!(
+            decompress_with_dictionary(&compressed, other)
+                .ok()
+                .as_deref(),
+            Some(&fragment[..])
+        );
+    }
+
+    #[test]
+    fn test_empty_dictionary() {
+        let original = b"No dictionary at all, no dictionary at all.";
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+        let compressed = compress_with_dictionary(original, &[], &options).unwrap();
+        assert_eq!(
+            compressed,
+            compress_with_options(original, &options).unwrap()
+        );
+        assert_eq!(
+            decompress_with_dictionary(&compressed, &[]).unwrap(),
+            original
+        );
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(
+            compress_with_dictionary(original, b"dict", &raw),
+            Err(Error::InvalidMode)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_options_serde() {
+        let options = Options::default();
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            json,
+            r#"{"version":"v1","mode":"normal","quality":"ratio","min_match_size":3}"#
+        );
+        assert_eq!(serde_json::from_str::<Options>(&json).unwrap(), options);
+
+        let tuned = Options {
+            effort: 6,
+            ..options
+        };
+        let json = serde_json::to_string(&tuned).unwrap();
+        assert!(json.ends_with(r#","effort":6}"#));
+        assert_eq!(serde_json::from_str::<Options>(&json).unwrap(), tuned);
+
+        assert_eq!(
+            serde_json::to_string(&Mode::RawForward).unwrap(),
+            r#""raw-forward""#
+        );
+        assert_eq!(
+            serde_json::from_str::<Quality>(r#""speed""#).unwrap(),
+            Quality::Speed
+        );
+        assert_eq!(
+            serde_json::from_str::<Version>(r#""v2""#).unwrap(),
+            Version::V2
+        );
+    }
+
+    // Only the bundled build links csrc/lzsa_sufsort.c
+    #[cfg(all(feature = "fast-only", not(feature = "system-lib")))]
+    #[test]
+    fn test_fast_only_suffix_array() {
+        unsafe extern "C" {
+            fn divsufsort_build_array(
+                ctx: *mut core::ffi::c_void,
+                text: *const u8,
+                suffix_array: *mut i32,
+                len: i32,
+            ) -> i32;
+        }
+
+        let mut state = 0x2545_f491u32;
+        let inputs: [Vec<u8>; 4] = [
+            b"banana bandana".to_vec(),
+            vec![b'x'; 1000],
+            b"abcab".repeat(300),
+            (0..2000)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    b"ab"[(state & 1) as usize]
+                })
+                .collect(),
+        ];
+
+        for text in inputs {
+            let mut expected: Vec<i32> = (0..text.len() as i32).collect();
+            expected.sort_by_key(|&i| &text[i as usize..]);
+
+            let mut suffix_array = vec![0i32; text.len()];
+            let result = unsafe {
+                divsufsort_build_array(
+                    core::ptr::null_mut(),
+                    text.as_ptr(),
+                    suffix_array.as_mut_ptr(),
+                    text.len() as i32,
+                )
+            };
+            assert_eq!(result, 0);
+            assert_eq!(suffix_array, expected);
+        }
+    }
+
+    #[test]
+    fn test_auto_traits() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Options>();
+        assert_send_sync::<Error>();
+        assert_send_sync::<Compressor>();
+        assert_send_sync::<Decompressor>();
+        assert_send_sync::<Context>();
+        assert_send_sync::<seekable::SeekableArchive<'static>>();
+        assert_send_sync::<stream::LzsaWriter<Vec<u8>>>();
+        assert_send_sync::<stream::LzsaReader<&[u8]>>();
+    }
+
+    #[test]
+    fn test_error_code() {
+        for code in -12..=-1 {
+            let error = Error::from(code);
+            assert!(!matches!(error, Error::Unknown(_)));
+            assert_eq!(error.code(), Some(code));
+        }
+        assert_eq!(Error::from(-99), Error::Unknown(-99));
+        assert_eq!(Error::Unknown(-99).code(), Some(-99));
+        assert_eq!(Error::ChecksumMismatch.code(), None);
+        assert_eq!(Error::OutOfRange.code(), None);
+        assert_eq!(Error::OutputTooLarge.code(), None);
+        assert_eq!(Error::InvalidUtf8.code(), None);
+        assert_eq!(Error::Truncated.code(), None);
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let empty: &[u8] = &[];
+        let compressed = compress_v1(empty).unwrap();
+        assert_eq!(compressed.len(), 0);
+        let decompressed = decompress(&compressed).unwrap();
+        assert_eq!(decompressed.len(), 0);
+    }
+
+    #[test]
+    fn test_empty_stream() {
+        // Header, then the footer straight away
+        let v1: &[u8] = &[0x7b, 0x9e, 0x00, 0x00, 0x00, 0x00];
+        let v2: &[u8] = &[0x7b, 0x9e, 0x20, 0x00, 0x00, 0x00];
+
+        assert_eq!(decompress(v1).unwrap(), b"");
+        assert_eq!(decompress_detect(v2).unwrap(), (vec![], Version::V2));
+        assert_eq!(decompress_v1(v1).unwrap(), b"");
+        assert_eq!(decompress_v2(v1), Err(Error::InvalidFormat));
+        assert_eq!(decompress_with_dictionary(v2, b"dict").unwrap(), b"");
+
+        let mut decompressor = Decompressor::new();
+        assert_eq!(decompressor.decompress(v2).unwrap(), b"");
+        assert_eq!(decompressor.version(), Some(Version::V2));
+        let mut context = Context::new(Options::default()).unwrap();
+        assert_eq!(context.decompress(v1).unwrap(), b"");
+
+        for stream in [v1, v2] {
+            assert_eq!(max_decompressed_size(stream), Ok(0));
+            assert_eq!(decompress_limited(stream, 0).unwrap(), b"");
+            assert_eq!(decompress_into(stream, &mut []), Ok(0));
+            assert_eq!(decompress_from_reader(&mut &stream[..]).unwrap(), b"");
+
+            let mut buffer = stream.to_vec();
+            assert_eq!(decompress_in_place(&mut buffer, stream.len()), Ok(0));
+            assert!(buffer.is_empty());
+        }
+
+        // The header is still checked
+        assert_eq!(
+            decompress(&[0x7b, 0x9f, 0x00, 0x00, 0x00, 0x00]),
+            Err(Error::InvalidFormat)
+        );
+        assert!(decompress(&[0x7b, 0x9e, 0x40, 0x00, 0x00, 0x00]).is_err());
+    }
+
+    /// Shape (size and alignment of each argument and the return value) of
+    /// an FFI function, so hand-written and generated declarations compare
+    #[cfg(feature = "bindgen")]
+    macro_rules! signature {
+        ($f:expr; $($arg:ident),*) => {{
+            #[allow(non_camel_case_types)]
+            fn shape<$($arg,)* R>(_: unsafe extern "C" fn($($arg),*) -> R) -> Vec<(usize, usize)> {
+                vec![
+                    $((core::mem::size_of::<$arg>(), core::mem::align_of::<$arg>()),)*
+                    (core::mem::size_of::<R>(), core::mem::align_of::<R>()),
+                ]
+            }
+            shape($f)
+        }};
+    }
+
+    #[cfg(feature = "bindgen")]
+    macro_rules! assert_same_signature {
+        ($name:ident; $($arg:ident),*) => {
+            assert_eq!(
+                signature!(super::$name; $($arg),*),
+                signature!(bindings::$name; $($arg),*),
+                stringify!($name)
+            );
+        };
+    }
+
+    #[test]
+    #[cfg(feature = "bindgen")]
+    // The C enum type is unsigned with GCC/Clang but signed with MSVC
+    #[allow(clippy::unnecessary_cast)]
+    fn test_bindings_match_hand_written() {
+        use core::mem::{align_of, offset_of, size_of};
+
+        assert_eq!(size_of::<Options>(), size_of::<bindings::lzsa_options_t>());
+        assert_eq!(
+            align_of::<Options>(),
+            align_of::<bindings::lzsa_options_t>()
+        );
+        assert_eq!(
+            offset_of!(Options, version),
+            offset_of!(bindings::lzsa_options_t, version)
+        );
+        assert_eq!(
+            offset_of!(Options, mode),
+            offset_of!(bindings::lzsa_options_t, mode)
+        );
+        assert_eq!(
+            offset_of!(Options, quality),
+            offset_of!(bindings::lzsa_options_t, quality)
+        );
+        assert_eq!(
+            offset_of!(Options, min_match_size),
+            offset_of!(bindings::lzsa_options_t, min_match_size)
+        );
+        assert_eq!(
+            offset_of!(Options, effort),
+            offset_of!(bindings::lzsa_options_t, effort)
+        );
+        assert_eq!(
+            offset_of!(Options, block_size),
+            offset_of!(bindings::lzsa_options_t, block_size)
+        );
+
+        assert_eq!(size_of::<RawStats>(), size_of::<bindings::lzsa_stats_t>());
+        assert_eq!(
+            offset_of!(RawStats, command_count),
+            offset_of!(bindings::lzsa_stats_t, command_count)
+        );
+        assert_eq!(
+            offset_of!(RawStats, match_count),
+            offset_of!(bindings::lzsa_stats_t, match_count)
+        );
+        assert_eq!(
+            offset_of!(RawStats, literal_count),
+            offset_of!(bindings::lzsa_stats_t, literal_count)
+        );
+
+        assert_eq!(Version::V1 as u32, bindings::LZSA_VERSION_1 as u32);
+        assert_eq!(Version::V2 as u32, bindings::LZSA_VERSION_2 as u32);
+        assert_eq!(Mode::Normal as u32, bindings::LZSA_MODE_NORMAL as u32);
+        assert_eq!(
+            Mode::RawForward as u32,
+            bindings::LZSA_MODE_RAW_FORWARD as u32
+        );
+        assert_eq!(
+            Mode::RawBackward as u32,
+            bindings::LZSA_MODE_RAW_BACKWARD as u32
+        );
+        assert_eq!(Quality::Speed as u32, bindings::LZSA_QUALITY_SPEED as u32);
+        assert_eq!(Quality::Ratio as u32, bindings::LZSA_QUALITY_RATIO as u32);
+        assert_eq!(MAX_MIN_MATCH, bindings::LZSA_MAX_MIN_MATCH_SIZE);
+
+        for code in [
+            bindings::LZSAWRAP_ERR_INPUT_NULL,
+            bindings::LZSAWRAP_ERR_OUTPUT_NULL,
+            bindings::LZSAWRAP_ERR_OUTPUT_SIZE_NULL,
+            bindings::LZSAWRAP_ERR_BUFFER_TOO_SMALL,
+            bindings::LZSAWRAP_ERR_COMPRESSION_FAILED,
+            bindings::LZSAWRAP_ERR_DECOMPRESSION_FAILED,
+            bindings::LZSAWRAP_ERR_INVALID_FORMAT,
+            bindings::LZSAWRAP_ERR_INVALID_VERSION,
+            bindings::LZSAWRAP_ERR_OUT_OF_MEMORY,
+            bindings::LZSAWRAP_ERR_INVALID_MODE,
+            bindings::LZSAWRAP_ERR_INVALID_BLOCK_SIZE,
+            bindings::LZSAWRAP_ERR_INVALID_MIN_MATCH,
+        ] {
+            assert!(
+                !matches!(Error::from(code as i32), Error::Unknown(_)),
+                "unmapped error code {code}"
+            );
+        }
+
+        assert_same_signature!(lzsa_get_default_options;);
+        assert_same_signature!(lzsa_get_max_compressed_size; A, B, C);
+        assert_same_signature!(lzsa_get_max_decompressed_size; A, B);
+        assert_same_signature!(lzsa_compress; A, B, C, D, E);
+        assert_same_signature!(lzsa_compress_v1; A, B, C, D);
+        assert_same_signature!(lzsa_compress_v2; A, B, C, D);
+        assert_same_signature!(lzsa_decompress; A, B, C, D, E);
+        assert_same_signature!(lzsa_decompress_v1; A, B, C, D);
+        assert_same_signature!(lzsa_decompress_v2; A, B, C, D);
+        assert_same_signature!(lzsa_decompress_v1_backward; A, B, C, D);
+        assert_same_signature!(lzsa_decompress_v2_backward; A, B, C, D);
+        assert_same_signature!(lzsa_decompress_raw; A, B, C, D, E);
+        assert_same_signature!(lzsa_compress_with_stats; A, B, C, D, E, F);
+        assert_same_signature!(lzsa_compress_with_dictionary; A, B, C, D, E, F, G);
+        assert_same_signature!(lzsa_decompress_with_dictionary; A, B, C, D, E, F, G);
+        assert_same_signature!(lzsa_encode_stream_header; A, B, C);
+        assert_same_signature!(lzsa_encode_stream_footer; A, B);
+        assert_same_signature!(lzsa_compress_block; A, B, C, D, E, F);
+        assert_same_signature!(lzsa_compress_block_data; A, B, C, D, E, F);
+        assert_same_signature!(lzsa_decode_stream_header; A, B, C);
+        assert_same_signature!(lzsa_decode_block_frame; A, B, C, D);
+        assert_same_signature!(lzsa_decompress_block; A, B, C, D, E, F);
+        assert_same_signature!(lzsa_context_create; A, B);
+        assert_same_signature!(lzsa_options_layout; A, B, C);
+        assert_same_signature!(lzsa_stats_layout; A, B, C);
+        assert_same_signature!(lzsa_context_destroy; A);
+        assert_same_signature!(lzsa_context_compress; A, B, C, D, E);
+        assert_same_signature!(lzsa_context_decompress; A, B, C, D, E, F);
     }
 }
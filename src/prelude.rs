@@ -0,0 +1,33 @@
+//! The commonly used items, for glob import
+//!
+//! ```rust
+//! use lzsa_sys::prelude::*;
+//!
+//! # #[cfg(feature = "v1")] {
+//! let options = Options::builder()
+//!     .version(Version::V1)
+//!     .quality(Quality::Speed)
+//!     .build();
+//! let compressed = compress_with_options(b"prelude, prelude, prelude", &options)?;
+//! assert_eq!(decompress(&compressed)?, b"prelude, prelude, prelude");
+//! # }
+//! # Ok::<(), Error>(())
+//! ```
+//!
+//! Only the format types, the one-shot functions and the reusable
+//! compressor and decompressor are included. The crate's `Result` alias is
+//! left out so that it does not shadow the standard one.
+
+pub use crate::{Error, Mode, Options, Quality, Version};
+
+#[cfg(feature = "alloc")]
+pub use crate::{Compressor, Decompressor, compress_with_options, decompress};
+
+#[cfg(all(feature = "alloc", feature = "v1"))]
+pub use crate::compress_v1;
+
+#[cfg(all(feature = "alloc", feature = "v2"))]
+pub use crate::compress_v2;
+
+#[cfg(feature = "std")]
+pub use crate::stream::{LzsaReader, LzsaWriter};
@@ -0,0 +1,322 @@
+//! Seekable archives with a block index for random access
+//!
+//! [`SeekableArchive::create`] splits the input into logical blocks of a
+//! fixed size and compresses each one as an independent framed LZSA stream.
+//! An index of where each stream starts follows the streams, so
+//! [`SeekableArchive::read_range`] only decompresses the blocks a range
+//! touches.
+//!
+//! ```rust
+//! use lzsa_sys::Options;
+//! use lzsa_sys::seekable::SeekableArchive;
+//!
+//! let data: Vec<u8> = (0..100_000u32).map(|i| (i % 97) as u8).collect();
+//! let bytes = SeekableArchive::create(&data, 16 * 1024, &Options::default())?;
+//!
+//! let archive = SeekableArchive::open(&bytes)?;
+//! assert_eq!(archive.read_range(50_000, 10)?, &data[50_000..50_010]);
+//! # Ok::<(), lzsa_sys::Error>(())
+//! ```
+//!
+//! # Layout
+//!
+//! ```text
+//! stream 0 | stream 1 | ... | index | trailer
+//! ```
+//!
+//! The index holds the compressed offset of each stream as a `u64`. The
+//! 24-byte trailer holds the block size and the total decompressed length
+//! (both `u64`), the archive format version (`u32`) and the magic bytes
+//! `LZSX`. All integers are little-endian. Block `i` covers decompressed
+//! bytes `i * block_size` up to the next block or the end of the data.
+
+use alloc::vec::Vec;
+
+use crate::{Error, Mode, Options, Result, compress_with_options, decompress};
+
+/// Magic bytes ending every archive
+const ARCHIVE_MAGIC: [u8; 4] = *b"LZSX";
+
+/// Version of the index and trailer layout written by this crate
+const ARCHIVE_VERSION: u32 = 1;
+
+const TRAILER_SIZE: usize = 24;
+
+const INDEX_ENTRY_SIZE: usize = 8;
+
+/// A parsed archive borrowing its bytes
+#[derive(Debug, Clone)]
+pub struct SeekableArchive<'a> {
+    data: &'a [u8],
+    block_size: usize,
+    len: usize,
+    /// Compressed offset of each block, followed by the start of the index
+    offsets: Vec<usize>,
+}
+
+impl<'a> SeekableArchive<'a> {
+    /// Compress `input` into an archive of `block_size`-byte blocks
+    ///
+    /// Smaller blocks make reads of short ranges cheaper but compress worse,
+    /// since matches cannot cross block boundaries. Returns
+    /// `Error::InvalidBlockSize` if `block_size` is 0 and
+    /// `Error::InvalidMode` for raw modes, which have no frames.
+    pub fn create(input: &[u8], block_size: usize, options: &Options) -> Result<Vec<u8>> {
+        if block_size == 0 {
+            return Err(Error::InvalidBlockSize);
+        }
+        if options.mode != Mode::Normal {
+            return Err(Error::InvalidMode);
+        }
+
+        let mut output = Vec::new();
+        let mut index = Vec::with_capacity(input.len().div_ceil(block_size) * INDEX_ENTRY_SIZE);
+        for chunk in input.chunks(block_size) {
+            index.extend_from_slice(&(output.len() as u64).to_le_bytes());
+            output.extend_from_slice(&compress_with_options(chunk, options)?);
+        }
+
+        output.extend_from_slice(&index);
+        output.extend_from_slice(&(block_size as u64).to_le_bytes());
+        output.extend_from_slice(&(input.len() as u64).to_le_bytes());
+        output.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        output.extend_from_slice(&ARCHIVE_MAGIC);
+        Ok(output)
+    }
+
+    /// Parse the index and trailer of an archive made by [`create`](Self::create)
+    ///
+    /// Nothing is decompressed. Returns `Error::InvalidVersion` for an
+    /// archive format version this crate does not know, and
+    /// `Error::InvalidFormat` if the trailer or index is damaged.
+    pub fn open(data: &'a [u8]) -> Result<Self> {
+        let trailer_start = data
+            .len()
+            .checked_sub(TRAILER_SIZE)
+            .ok_or(Error::InvalidFormat)?;
+        let trailer = &data[trailer_start..];
+
+        if trailer[20..] != ARCHIVE_MAGIC {
+            return Err(Error::InvalidFormat);
+        }
+        if read_u32(&trailer[16..]) != ARCHIVE_VERSION {
+            return Err(Error::InvalidVersion);
+        }
+
+        let block_size = to_usize(read_u64(trailer))?;
+        let len = to_usize(read_u64(&trailer[8..]))?;
+        if block_size == 0 {
+            return Err(Error::InvalidFormat);
+        }
+
+        let block_count = len.div_ceil(block_size);
+        let index_start = block_count
+            .checked_mul(INDEX_ENTRY_SIZE)
+            .and_then(|index_len| trailer_start.checked_sub(index_len))
+            .ok_or(Error::InvalidFormat)?;
+
+        let mut offsets = data[index_start..trailer_start]
+            .chunks_exact(INDEX_ENTRY_SIZE)
+            .map(|entry| to_usize(read_u64(entry)))
+            .collect::<Result<Vec<_>>>()?;
+        offsets.push(index_start);
+
+        // Streams are laid out back to back from the start of the data
+        if offsets[0] != 0 || offsets.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(Error::InvalidFormat);
+        }
+
+        Ok(Self {
+            data,
+            block_size,
+            len,
+            offsets,
+        })
+    }
+
+    /// Total decompressed length
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the archive holds no data
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Decompressed size of each block (the last one may be shorter)
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Number of blocks in the archive
+    pub fn block_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Decompress `len` bytes starting at decompressed offset `start`
+    ///
+    /// Only the blocks overlapping the range are decompressed. Returns
+    /// `Error::OutOfRange` if the range reaches past [`len`](Self::len),
+    /// and `Error::InvalidFormat` if a block does not decompress to its
+    /// indexed size.
+    pub fn read_range(&self, start: usize, len: usize) -> Result<Vec<u8>> {
+        let end = start
+            .checked_add(len)
+            .filter(|&end| end <= self.len)
+            .ok_or(Error::OutOfRange)?;
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let first = start / self.block_size;
+        let last = (end - 1) / self.block_size;
+
+        let mut output = Vec::with_capacity(len);
+        for block in first..=last {
+            let data = decompress(&self.data[self.offsets[block]..self.offsets[block + 1]])?;
+
+            let block_start = block * self.block_size;
+            let block_len = self.block_size.min(self.len - block_start);
+            if data.len() != block_len {
+                return Err(Error::InvalidFormat);
+            }
+
+            let from = start.max(block_start) - block_start;
+            let to = end.min(block_start + block_len) - block_start;
+            output.extend_from_slice(&data[from..to]);
+        }
+
+        Ok(output)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(value)
+}
+
+fn to_usize(value: u64) -> Result<usize> {
+    usize::try_from(value).map_err(|_| Error::InvalidFormat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i % 251) as u8 ^ (i / 1000) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_read_range() {
+        let original = sample(100_000);
+        let bytes = SeekableArchive::create(&original, 8192, &Options::default()).unwrap();
+        let archive = SeekableArchive::open(&bytes).unwrap();
+
+        assert_eq!(archive.len(), original.len());
+        assert_eq!(archive.block_count(), 13);
+
+        for (start, len) in [
+            (0, 10),
+            (8190, 5),
+            (5000, 30_000),
+            (99_990, 10),
+            (0, 100_000),
+        ] {
+            assert_eq!(
+                archive.read_range(start, len).unwrap(),
+                &original[start..start + len]
+            );
+        }
+        assert!(archive.read_range(100_000, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let bytes = SeekableArchive::create(&sample(1000), 256, &Options::default()).unwrap();
+        let archive = SeekableArchive::open(&bytes).unwrap();
+
+        assert_eq!(archive.read_range(990, 11), Err(Error::OutOfRange));
+        assert_eq!(archive.read_range(usize::MAX, 2), Err(Error::OutOfRange));
+    }
+
+    #[test]
+    fn test_empty_archive() {
+        let bytes = SeekableArchive::create(&[], 4096, &Options::default()).unwrap();
+        assert_eq!(bytes.len(), TRAILER_SIZE);
+
+        let archive = SeekableArchive::open(&bytes).unwrap();
+        assert!(archive.is_empty());
+        assert_eq!(archive.block_count(), 0);
+        assert!(archive.read_range(0, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let options = Options::default();
+        assert_eq!(
+            SeekableArchive::create(b"data", 0, &options),
+            Err(Error::InvalidBlockSize)
+        );
+
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..options
+        };
+        assert_eq!(
+            SeekableArchive::create(b"data", 16, &raw),
+            Err(Error::InvalidMode)
+        );
+    }
+
+    #[test]
+    fn test_damaged_archive() {
+        let bytes = SeekableArchive::create(&sample(5000), 1024, &Options::default()).unwrap();
+        let trailer_start = bytes.len() - TRAILER_SIZE;
+
+        assert_eq!(
+            SeekableArchive::open(&bytes[..10]).err(),
+            Some(Error::InvalidFormat)
+        );
+
+        let mut bad_magic = bytes.clone();
+        *bad_magic.last_mut().unwrap() ^= 0xff;
+        assert_eq!(
+            SeekableArchive::open(&bad_magic).err(),
+            Some(Error::InvalidFormat)
+        );
+
+        let mut future = bytes.clone();
+        future[trailer_start + 16] = 2;
+        assert_eq!(
+            SeekableArchive::open(&future).err(),
+            Some(Error::InvalidVersion)
+        );
+
+        // A length needing more index entries than the archive holds
+        let mut too_long = bytes.clone();
+        too_long[trailer_start + 8..trailer_start + 16]
+            .copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        assert_eq!(
+            SeekableArchive::open(&too_long).err(),
+            Some(Error::InvalidFormat)
+        );
+
+        // Offsets out of order
+        let index_start = trailer_start - 5 * INDEX_ENTRY_SIZE;
+        let mut swapped = bytes.clone();
+        swapped.copy_within(index_start..index_start + 8, index_start + 8);
+        assert_eq!(
+            SeekableArchive::open(&swapped).err(),
+            Some(Error::InvalidFormat)
+        );
+    }
+}
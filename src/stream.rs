@@ -0,0 +1,724 @@
+//! Streaming compression and decompression over `std::io`
+//!
+//! [`LzsaWriter`] produces a regular framed LZSA stream (the same layout the
+//! `lzsa` command-line tool writes), one block at a time, so arbitrarily large
+//! inputs can be compressed without holding them in memory. [`LzsaReader`]
+//! decodes such streams one block at a time.
+//!
+//! [`LzsaWriter::with_block_checksums`] adds a CRC-32 after every block,
+//! which [`LzsaReader`] checks as it decodes, so corruption in a long stream
+//! is caught at the block it hits. Such streams start with
+//! [`BLOCK_CHECKSUM_MAGIC`] and can only be read by `LzsaReader`.
+//!
+//! ```rust
+//! use std::io::{Read, Write};
+//! use lzsa_sys::Options;
+//! use lzsa_sys::stream::{LzsaReader, LzsaWriter};
+//!
+//! let mut writer = LzsaWriter::new(Vec::new(), Options::default())?;
+//! writer.write_all(b"Hello, ")?;
+//! writer.write_all(b"streaming world!")?;
+//! let compressed = writer.finish()?;
+//!
+//! let mut decompressed = Vec::new();
+//! LzsaReader::new(compressed.as_slice()).read_to_end(&mut decompressed)?;
+//! assert_eq!(decompressed, b"Hello, streaming world!");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use core::ffi::c_int;
+use std::io::{self, Read, Write};
+
+use crate::{
+    BLOCK_SIZE, Error, FRAME_SIZE, Mode, Options, Result, Version, checked_compress,
+    checked_decompress, crc32,
+};
+
+/// Prefix of streams written with [`LzsaWriter::with_block_checksums`],
+/// ahead of the regular stream header
+///
+/// Every block of such a stream is followed by the CRC-32 (little-endian)
+/// of its decompressed data. The footer has none.
+pub const BLOCK_CHECKSUM_MAGIC: [u8; 4] = *b"LZB4";
+
+/// Size of the checksum after each block
+const BLOCK_CHECKSUM_SIZE: usize = 4;
+
+/// Compressing writer that emits a framed LZSA stream
+///
+/// Input is buffered until a full block has accumulated, so many small
+/// `write()` calls are coalesced into the same block. Each block may
+/// reference the previous one, as in streams produced by the `lzsa` tool.
+///
+/// The stream footer is written by [`finish`](Self::finish). Dropping the
+/// writer finishes the stream too, but any error is then silently ignored.
+pub struct LzsaWriter<W: Write> {
+    inner: Option<W>,
+    options: Options,
+    block_size: usize,
+    /// Previous block (`previous_len` bytes) followed by pending input
+    window: Vec<u8>,
+    previous_len: usize,
+    scratch: Vec<u8>,
+    header_written: bool,
+    block_checksums: bool,
+}
+
+impl<W: Write> LzsaWriter<W> {
+    /// Default block size (64 KiB, the largest the format allows)
+    pub const DEFAULT_BLOCK_SIZE: usize = BLOCK_SIZE;
+
+    /// Create a writer emitting blocks of `options.block_size` input bytes
+    ///
+    /// Returns `Error::InvalidMode` unless `options.mode` is `Mode::Normal`,
+    /// since raw blocks cannot be streamed, and `Error::InvalidMinMatch` if
+    /// `options.min_match_size` is out of range for the version.
+    pub fn new(inner: W, options: Options) -> Result<Self> {
+        Self::with_block_size(inner, options, options.block_size as usize)
+    }
+
+    /// Create a writer that emits blocks of `block_size` input bytes,
+    /// ignoring `options.block_size`
+    ///
+    /// `block_size` must be between 1 and [`BLOCK_SIZE`], otherwise
+    /// `Error::InvalidBlockSize` is returned.
+    pub fn with_block_size(inner: W, options: Options, block_size: usize) -> Result<Self> {
+        if options.mode != Mode::Normal {
+            return Err(Error::InvalidMode);
+        }
+        if !options
+            .version
+            .min_match_sizes()
+            .contains(&options.min_match_size)
+        {
+            return Err(Error::InvalidMinMatch);
+        }
+        if block_size == 0 || block_size > BLOCK_SIZE {
+            return Err(Error::InvalidBlockSize);
+        }
+
+        Ok(Self {
+            inner: Some(inner),
+            options,
+            block_size,
+            window: Vec::with_capacity(block_size * 2),
+            previous_len: 0,
+            scratch: vec![0u8; block_size + FRAME_SIZE],
+            header_written: false,
+            block_checksums: false,
+        })
+    }
+
+    /// Follow every block with a CRC-32 of its data, for [`LzsaReader`] to
+    /// verify while decoding
+    ///
+    /// The stream is marked with [`BLOCK_CHECKSUM_MAGIC`], so readers tell
+    /// it apart from a plain one; other LZSA decompressors cannot read it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a block was already written.
+    pub fn with_block_checksums(mut self) -> Self {
+        assert!(!self.header_written, "stream already started");
+        self.block_checksums = true;
+        self
+    }
+
+    /// Get a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("writer already finished")
+    }
+
+    /// Get a mutable reference to the underlying writer
+    ///
+    /// Writing to it directly will corrupt the compressed stream.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer already finished")
+    }
+
+    /// Compress any pending input, write the stream footer and return the
+    /// underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let result = self.finish_stream();
+        // Taking the writer out also keeps Drop from finishing a second time
+        let inner = self.inner.take().expect("writer already finished");
+        result.map(|()| inner)
+    }
+
+    /// Compress all buffered input into a block now, however little there is
+    ///
+    /// Returns the number of bytes written to the underlying writer (the
+    /// stream header included, the first time), or 0 if nothing was
+    /// buffered. Summing these counts gives the offset of every block, so
+    /// boundaries can be aligned to records and indexed for random access.
+    /// The underlying writer is not flushed.
+    ///
+    /// Short blocks compress worse than full ones, so flushing often costs
+    /// ratio.
+    pub fn flush_block(&mut self) -> io::Result<usize> {
+        self.write_block()
+    }
+
+    fn pending_len(&self) -> usize {
+        self.window.len() - self.previous_len
+    }
+
+    /// Write the stream header unless already done; returns its size
+    fn write_header(&mut self) -> io::Result<usize> {
+        if self.header_written {
+            return Ok(0);
+        }
+
+        let mut header = [0u8; FRAME_SIZE];
+        let mut header_size = header.len();
+        let result = unsafe {
+            crate::lzsa_encode_stream_header(
+                header.as_mut_ptr(),
+                &mut header_size,
+                self.options.version as c_int,
+            )
+        };
+        let header_size = checked_compress(result, header_size, header.len())?;
+
+        let magic_size = if self.block_checksums {
+            self.get_mut().write_all(&BLOCK_CHECKSUM_MAGIC)?;
+            BLOCK_CHECKSUM_MAGIC.len()
+        } else {
+            0
+        };
+
+        self.get_mut().write_all(&header[..header_size])?;
+        self.header_written = true;
+        Ok(magic_size + header_size)
+    }
+
+    /// Compress the pending input as one block and write it out; returns the
+    /// number of bytes written
+    fn write_block(&mut self) -> io::Result<usize> {
+        let pending = self.pending_len();
+        if pending == 0 {
+            return Ok(0);
+        }
+
+        let header_size = self.write_header()?;
+
+        let mut output_size = self.scratch.len();
+        let result = unsafe {
+            crate::lzsa_compress_block(
+                self.window.as_ptr(),
+                self.previous_len,
+                pending,
+                self.scratch.as_mut_ptr(),
+                &mut output_size,
+                &self.options as *const Options,
+            )
+        };
+        let output_size = checked_compress(result, output_size, self.scratch.len())?;
+
+        let inner = self.inner.as_mut().expect("writer already finished");
+        inner.write_all(&self.scratch[..output_size])?;
+
+        let checksum_size = if self.block_checksums {
+            let checksum = crc32::crc32(&self.window[self.previous_len..]);
+            inner.write_all(&checksum.to_le_bytes())?;
+            BLOCK_CHECKSUM_SIZE
+        } else {
+            0
+        };
+
+        // The block just written becomes the match window for the next one
+        self.window.drain(..self.previous_len);
+        self.previous_len = self.window.len();
+        Ok(header_size + output_size + checksum_size)
+    }
+
+    fn finish_stream(&mut self) -> io::Result<()> {
+        if self.inner.is_none() {
+            return Ok(());
+        }
+
+        self.write_block()?;
+        self.write_header()?;
+
+        let mut footer = [0u8; FRAME_SIZE];
+        let mut footer_size = footer.len();
+        let result =
+            unsafe { crate::lzsa_encode_stream_footer(footer.as_mut_ptr(), &mut footer_size) };
+        let footer_size = checked_compress(result, footer_size, footer.len())?;
+
+        let inner = self.get_mut();
+        inner.write_all(&footer[..footer_size])?;
+        inner.flush()
+    }
+}
+
+impl<W: Write> Write for LzsaWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let room = self.block_size - self.pending_len();
+        let len = buf.len().min(room);
+        self.window.extend_from_slice(&buf[..len]);
+
+        if self.pending_len() == self.block_size {
+            self.write_block()?;
+        }
+
+        Ok(len)
+    }
+
+    /// Compress buffered input as a (possibly partial) block and flush the
+    /// underlying writer
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for LzsaWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish_stream();
+    }
+}
+
+/// Decompressing reader for framed LZSA streams
+///
+/// The format version is detected from each stream header, as `decompress`
+/// does. Blocks are decoded one at a time into an internal buffer that
+/// `read()` calls are served from, so at most two decoded blocks (the current
+/// one and the previous one it may reference) are held in memory.
+///
+/// Several streams concatenated back to back are read as one. Streams with
+/// block checksums are recognized by [`BLOCK_CHECKSUM_MAGIC`]; a block that
+/// does not match its checksum fails the read with `Error::ChecksumMismatch`
+/// (inside the `io::Error`) and [`failed_block`](Self::failed_block) tells
+/// which one it was.
+pub struct LzsaReader<R: Read> {
+    inner: R,
+    version: Option<Version>,
+    in_stream: bool,
+    /// Whether the current stream has a checksum after each block
+    block_checksums: bool,
+    /// Blocks decoded so far, over all streams
+    block_index: u64,
+    failed_block: Option<u64>,
+    /// Compressed data of the block being decoded
+    block: Vec<u8>,
+    /// Previous decoded block (`previous_len` bytes) followed by the current one
+    window: Vec<u8>,
+    previous_len: usize,
+    /// Read position inside the current block
+    pos: usize,
+}
+
+impl<R: Read> LzsaReader<R> {
+    /// Create a reader decoding the framed stream read from `inner`
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            version: None,
+            in_stream: false,
+            block_checksums: false,
+            block_index: 0,
+            failed_block: None,
+            block: Vec::new(),
+            window: Vec::with_capacity(BLOCK_SIZE * 2),
+            previous_len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Format version of the stream being read, once its header was seen
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Index of the block that failed its checksum, counting from 0 over
+    /// all streams read, once a read has returned `Error::ChecksumMismatch`
+    pub fn failed_block(&self) -> Option<u64> {
+        self.failed_block
+    }
+
+    /// Get a reference to the underlying reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying reader
+    ///
+    /// Reading from it directly will corrupt the decompressed stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Fill `buf` completely, or return `false` on a clean end of input
+    /// before the first byte
+    fn read_frame_bytes(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Decode the next block into the window; returns `false` at end of input
+    fn next_block(&mut self) -> io::Result<bool> {
+        let mut frame = [0u8; FRAME_SIZE];
+
+        if !self.in_stream {
+            if !self.read_frame_bytes(&mut frame)? {
+                return Ok(false);
+            }
+
+            self.block_checksums = frame == BLOCK_CHECKSUM_MAGIC[..FRAME_SIZE];
+            if self.block_checksums {
+                let mut rest = [0u8; BLOCK_CHECKSUM_MAGIC.len() - FRAME_SIZE];
+                self.inner.read_exact(&mut rest)?;
+                if rest != BLOCK_CHECKSUM_MAGIC[FRAME_SIZE..] {
+                    return Err(Error::InvalidFormat.into());
+                }
+                self.inner.read_exact(&mut frame)?;
+            }
+
+            let mut version: c_int = 0;
+            let result = unsafe {
+                crate::lzsa_decode_stream_header(frame.as_ptr(), frame.len(), &mut version)
+            };
+            if result != 0 {
+                return Err(Error::from(result).into());
+            }
+
+            self.version = Some(Version::from_raw(version).ok_or(Error::InvalidVersion)?);
+            self.in_stream = true;
+        }
+
+        if !self.read_frame_bytes(&mut frame)? {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut block_size = 0usize;
+        let mut is_uncompressed: c_int = 0;
+        let result = unsafe {
+            crate::lzsa_decode_block_frame(
+                frame.as_ptr(),
+                frame.len(),
+                &mut block_size,
+                &mut is_uncompressed,
+            )
+        };
+        if result != 0 {
+            return Err(Error::from(result).into());
+        }
+
+        // The block just served becomes the previous block, and a new stream
+        // starts without one
+        self.window.drain(..self.previous_len);
+        self.previous_len = self.window.len();
+
+        if block_size == 0 {
+            // Footer: the next stream, if any, starts from scratch
+            self.in_stream = false;
+            self.window.clear();
+            self.previous_len = 0;
+            self.pos = 0;
+            return Ok(true);
+        }
+
+        if block_size > BLOCK_SIZE {
+            return Err(Error::InvalidFormat.into());
+        }
+
+        self.block.resize(block_size, 0);
+        self.inner.read_exact(&mut self.block)?;
+
+        if is_uncompressed != 0 {
+            self.window.extend_from_slice(&self.block);
+        } else {
+            let version = self.version.expect("stream header already decoded");
+            self.window.resize(self.previous_len + BLOCK_SIZE, 0);
+
+            let mut output_size = BLOCK_SIZE;
+            let result = unsafe {
+                crate::lzsa_decompress_block(
+                    self.block.as_ptr(),
+                    self.block.len(),
+                    self.window.as_mut_ptr(),
+                    self.previous_len,
+                    &mut output_size,
+                    version as c_int,
+                )
+            };
+            match checked_decompress(result, output_size, BLOCK_SIZE) {
+                Ok(output_size) => self.window.truncate(self.previous_len + output_size),
+                Err(error) => {
+                    self.window.truncate(self.previous_len);
+                    return Err(error.into());
+                }
+            }
+        }
+
+        if self.block_checksums {
+            let mut checksum = [0u8; BLOCK_CHECKSUM_SIZE];
+            self.inner.read_exact(&mut checksum)?;
+            if crc32::crc32(&self.window[self.previous_len..]) != u32::from_le_bytes(checksum) {
+                // Serve nothing from the corrupt block
+                self.window.truncate(self.previous_len);
+                self.failed_block = Some(self.block_index);
+                return Err(Error::ChecksumMismatch.into());
+            }
+        }
+        self.block_index += 1;
+
+        self.pos = self.previous_len;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for LzsaReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.pos == self.window.len() {
+            if !self.next_block()? {
+                return Ok(0);
+            }
+        }
+
+        let available = &self.window[self.pos..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(all(test, feature = "v1", feature = "v2"))]
+mod tests {
+    use super::*;
+    use crate::{compress_v2, compress_with_options, decompress};
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i % 251) as u8 ^ (i / 1000) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let original = sample(200_000);
+        let options = Options {
+            version: Version::V2,
+            ..Options::default()
+        };
+
+        let mut writer = LzsaWriter::new(Vec::new(), options).unwrap();
+        writer.write_all(&original).unwrap();
+        let compressed = writer.finish().unwrap();
+
+        assert_eq!(decompress(&compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_writer_stores_blocks_that_do_not_shrink() {
+        for input in [&b"A"[..], b"AB", b"ABCD"] {
+            let mut writer = LzsaWriter::new(Vec::new(), Options::default()).unwrap();
+            writer.write_all(input).unwrap();
+            let compressed = writer.finish().unwrap();
+
+            assert_eq!(compressed.len(), 3 + 3 + input.len() + 3);
+            assert_eq!(
+                compressed,
+                compress_with_options(input, &Options::default()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_writer_coalesces_small_writes() {
+        let original = sample(10_000);
+
+        let mut bulk = LzsaWriter::with_block_size(Vec::new(), Options::default(), 4096).unwrap();
+        bulk.write_all(&original).unwrap();
+        let bulk = bulk.finish().unwrap();
+
+        let mut tiny = LzsaWriter::with_block_size(Vec::new(), Options::default(), 4096).unwrap();
+        for chunk in original.chunks(3) {
+            tiny.write_all(chunk).unwrap();
+        }
+        let tiny = tiny.finish().unwrap();
+
+        assert_eq!(bulk, tiny);
+    }
+
+    #[test]
+    fn test_writer_flush_block() {
+        let records: Vec<Vec<u8>> = (0..5).map(|i| sample(1000 + i * 300)).collect();
+
+        let mut writer = LzsaWriter::new(Vec::new(), Options::default()).unwrap();
+        assert_eq!(writer.flush_block().unwrap(), 0);
+
+        let mut offsets = Vec::new();
+        let mut pos = 0;
+        for record in &records {
+            writer.write_all(record).unwrap();
+            let written = writer.flush_block().unwrap();
+            assert_eq!(writer.get_ref().len(), pos + written);
+            offsets.push(pos);
+            pos += written;
+        }
+        assert_eq!(writer.flush_block().unwrap(), 0);
+        let compressed = writer.finish().unwrap();
+        assert_eq!(compressed.len(), pos + FRAME_SIZE);
+
+        // Every record starts a block of its own
+        for i in 0..records.len() {
+            let block_start = if i == 0 {
+                crate::HEADER_SIZE
+            } else {
+                offsets[i]
+            };
+            let (block_size, _) = crate::decode_block_frame(&compressed[block_start..]).unwrap();
+            let block_end = offsets.get(i + 1).copied().unwrap_or(pos);
+            assert_eq!(block_start + FRAME_SIZE + block_size, block_end);
+        }
+
+        assert_eq!(decompress(&compressed).unwrap(), records.concat());
+    }
+
+    #[test]
+    fn test_writer_flush_and_drop() {
+        let original = sample(5000);
+        let mut compressed = Vec::new();
+        {
+            let mut writer = LzsaWriter::new(&mut compressed, Options::default()).unwrap();
+            writer.write_all(&original[..1000]).unwrap();
+            writer.flush().unwrap();
+            writer.write_all(&original[1000..]).unwrap();
+        }
+
+        assert_eq!(decompress(&compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_reader_round_trip() {
+        let original = sample(150_000);
+        let compressed = compress_v2(&original).unwrap();
+
+        let mut reader = LzsaReader::new(compressed.as_slice());
+        let mut decompressed = Vec::new();
+        reader.read_to_end(&mut decompressed).unwrap();
+
+        assert_eq!(decompressed, original);
+        assert_eq!(reader.version(), Some(Version::V2));
+    }
+
+    #[test]
+    fn test_reader_small_reads_keep_remainder() {
+        let original = sample(20_000);
+        let mut writer = LzsaWriter::with_block_size(Vec::new(), Options::default(), 4096).unwrap();
+        writer.write_all(&original).unwrap();
+        let compressed = writer.finish().unwrap();
+
+        let mut reader = LzsaReader::new(compressed.as_slice());
+        let mut decompressed = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decompressed.extend_from_slice(&buf[..n]);
+        }
+
+        assert_eq!(decompressed, original);
+    }
+
+    #[test]
+    fn test_reader_rejects_garbage() {
+        let mut reader = LzsaReader::new(&b"not lzsa data"[..]);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+
+        let compressed = compress_v2(&sample(1000)).unwrap();
+        let truncated = &compressed[..compressed.len() - 10];
+        let mut reader = LzsaReader::new(truncated);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_block_checksums() {
+        let original = sample(20_000);
+        let mut writer = LzsaWriter::with_block_size(Vec::new(), Options::default(), 4096)
+            .unwrap()
+            .with_block_checksums();
+        let mut block_ends = Vec::new();
+        for chunk in original.chunks(4096) {
+            // Full blocks are written as soon as they fill
+            writer.write_all(chunk).unwrap();
+            writer.flush_block().unwrap();
+            block_ends.push(writer.get_ref().len());
+        }
+        let compressed = writer.finish().unwrap();
+        assert!(compressed.starts_with(&BLOCK_CHECKSUM_MAGIC));
+
+        // Followed by a plain stream, read as one
+        let mut input = compressed.clone();
+        input.extend_from_slice(&compress_v2(b"plain tail").unwrap());
+        let mut decompressed = Vec::new();
+        LzsaReader::new(input.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, [original.as_slice(), b"plain tail"].concat());
+
+        // Corrupt the checksum of the third block
+        let mut corrupt = compressed;
+        corrupt[block_ends[2] - 1] ^= 0xff;
+        let mut reader = LzsaReader::new(corrupt.as_slice());
+        let mut decompressed = Vec::new();
+        let error = reader.read_to_end(&mut decompressed).unwrap_err();
+        assert_eq!(
+            error.get_ref().and_then(|e| e.downcast_ref::<Error>()),
+            Some(&Error::ChecksumMismatch)
+        );
+        assert_eq!(reader.failed_block(), Some(2));
+        assert_eq!(decompressed, original[..2 * 4096]);
+    }
+
+    #[test]
+    fn test_writer_rejects_invalid_settings() {
+        let raw = Options {
+            mode: Mode::RawForward,
+            ..Options::default()
+        };
+        assert_eq!(
+            LzsaWriter::new(Vec::new(), raw).err(),
+            Some(Error::InvalidMode)
+        );
+        let bad_min_match = Options {
+            min_match_size: 0,
+            ..Options::default()
+        };
+        assert_eq!(
+            LzsaWriter::new(Vec::new(), bad_min_match).err(),
+            Some(Error::InvalidMinMatch)
+        );
+        assert_eq!(
+            LzsaWriter::with_block_size(Vec::new(), Options::default(), 0).err(),
+            Some(Error::InvalidBlockSize)
+        );
+        assert_eq!(
+            LzsaWriter::with_block_size(Vec::new(), Options::default(), BLOCK_SIZE + 1).err(),
+            Some(Error::InvalidBlockSize)
+        );
+    }
+}